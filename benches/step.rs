@@ -0,0 +1,255 @@
+// Step loop benchmarks
+//
+// Besides Criterion's own reports, `cargo bench` writes the mean time and throughput of every
+// benchmark run to `target/criterion/results.json` (or `$BENCH_RESULTS`) along with the current
+// commit, so results can be archived and compared across commits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use criterion::{criterion_group, BenchmarkId, Criterion, Throughput};
+use serde::Serialize;
+
+use korones::cpu::{Cpu, Variant};
+use korones::Nes;
+
+#[path = "../tests/common/flat_memory.rs"]
+mod flat_memory;
+
+use flat_memory::FlatMemory;
+
+// Synthetic instruction mixes, each an endless loop at $0400
+#[rustfmt::skip]
+const MIXES: [(&str, &[u8]); 7] = [
+    ("load_store", &[
+        0xA9, 0x42,       // $0400 LDA #$42
+        0x85, 0x10,       //       STA $10
+        0xA6, 0x10,       //       LDX $10
+        0x8E, 0x00, 0x03, //       STX $0300
+        0xBD, 0x00, 0x02, //       LDA $0200,X
+        0x99, 0x00, 0x03, //       STA $0300,Y
+        0xB1, 0x20,       //       LDA ($20),Y
+        0x81, 0x30,       //       STA ($30,X)
+        0xAC, 0x00, 0x03, //       LDY $0300
+        0x4C, 0x00, 0x04, //       JMP $0400
+    ]),
+    ("arithmetic", &[
+        0x18,             // $0400 CLC
+        0x69, 0x37,       //       ADC #$37
+        0x65, 0x10,       //       ADC $10
+        0x38,             //       SEC
+        0xE9, 0x11,       //       SBC #$11
+        0x29, 0xF7,       //       AND #$F7
+        0x09, 0x01,       //       ORA #$01
+        0x49, 0x5A,       //       EOR #$5A
+        0xC9, 0x80,       //       CMP #$80
+        0xE0, 0x10,       //       CPX #$10
+        0xC0, 0x20,       //       CPY #$20
+        0x4C, 0x00, 0x04, //       JMP $0400
+    ]),
+    ("decimal", &[
+        0xF8,             // $0400 SED
+        0x18,             // $0401 CLC
+        0x69, 0x37,       //       ADC #$37
+        0x65, 0x10,       //       ADC $10
+        0x38,             //       SEC
+        0xE9, 0x11,       //       SBC #$11
+        0xE5, 0x10,       //       SBC $10
+        0x4C, 0x01, 0x04, //       JMP $0401
+    ]),
+    ("read_modify_write", &[
+        0xE6, 0x10,       // $0400 INC $10
+        0xC6, 0x11,       //       DEC $11
+        0x06, 0x12,       //       ASL $12
+        0x66, 0x13,       //       ROR $13
+        0xFE, 0x00, 0x02, //       INC $0200,X
+        0x3E, 0x00, 0x03, //       ROL $0300,X
+        0xE8,             //       INX
+        0x4C, 0x00, 0x04, //       JMP $0400
+    ]),
+    ("branch", &[
+        0xA2, 0x10,       // $0400 LDX #$10
+        0xCA,             // $0402 DEX
+        0xF0, 0x02,       //       BEQ $0407
+        0xD0, 0xFB,       //       BNE $0402
+        0xA0, 0x90,       // $0407 LDY #$90
+        0x88,             // $0409 DEY
+        0x30, 0xFD,       //       BMI $0409
+        0x4C, 0x00, 0x04, //       JMP $0400
+    ]),
+    ("stack_jump", &[
+        0x20, 0x0A, 0x04, // $0400 JSR $040A
+        0x48,             //       PHA
+        0x08,             //       PHP
+        0x28,             //       PLP
+        0x68,             //       PLA
+        0x4C, 0x00, 0x04, //       JMP $0400
+        0x20, 0x0E, 0x04, // $040A JSR $040E
+        0x60,             //       RTS
+        0x60,             // $040E RTS
+    ]),
+    ("mixed", &[
+        0xA2, 0x00,       // $0400 LDX #$00
+        0xBD, 0x00, 0x02, // $0402 LDA $0200,X
+        0x69, 0x01,       //       ADC #$01
+        0x9D, 0x00, 0x03, //       STA $0300,X
+        0xE6, 0x10,       //       INC $10
+        0x0A,             //       ASL A
+        0x48,             //       PHA
+        0x68,             //       PLA
+        0xA4, 0x10,       //       LDY $10
+        0x51, 0x20,       //       EOR ($20),Y
+        0xE8,             //       INX
+        0xD0, 0xEC,       //       BNE $0402
+        0x20, 0x1C, 0x04, //       JSR $041C
+        0x4C, 0x00, 0x04, //       JMP $0400
+        0x60,             // $041C RTS
+    ]),
+];
+
+const VARIANTS: [Variant; 3] = [Variant::Ricoh2A03, Variant::Nmos6502, Variant::Cmos65C02];
+
+const INSTRUCTIONS: u64 = 100_000;
+
+fn cpu_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cpu_step");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    group.warm_up_time(Duration::from_secs(1));
+    group.measurement_time(Duration::from_secs(2));
+
+    for (mix, program) in MIXES {
+        for variant in VARIANTS {
+            let mut mem = FlatMemory::new(vec![0; 0x10000]);
+            mem.ram[0x0400..0x0400 + program.len()].copy_from_slice(program);
+            let mut cpu = Cpu::new(variant);
+            cpu.pc = 0x0400;
+
+            let id = BenchmarkId::new(mix, format!("{:?}", variant));
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    for _ in 0..INSTRUCTIONS {
+                        cpu.step(&mut mem);
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn nes_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nes_step");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+
+    // Without a cartridge the console keeps taking BRK from zeroed RAM, so this measures the overhead
+    // of the NES bus around the CPU
+    let mut nes = Nes::new();
+    nes.power_on();
+
+    group.bench_function("no_cartridge", |b| {
+        b.iter(|| {
+            for _ in 0..INSTRUCTIONS {
+                nes.step();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cpu_step, nes_step);
+
+fn main() {
+    let started = SystemTime::now();
+
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+
+    // Skip `cargo test --benches` and `--list`, which don't measure anything
+    if std::env::args().any(|a| a == "--bench") {
+        if let Err(e) = write_results(started) {
+            eprintln!("failed to write benchmark results: {}", e);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Results {
+    commit: Option<String>,
+    benchmarks: Vec<Measurement>,
+}
+
+#[derive(Serialize)]
+struct Measurement {
+    id: String,
+    mean_ns: f64,
+    elements_per_second: Option<f64>,
+}
+
+// Collects the benchmarks measured since `started` from Criterion's output directory
+fn write_results(started: SystemTime) -> std::io::Result<()> {
+    let root = criterion_home();
+    let mut benchmarks = Vec::new();
+    collect(&root, started, &mut benchmarks)?;
+    benchmarks.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+
+    let path = std::env::var_os("BENCH_RESULTS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join("results.json"));
+    let results = Results { commit, benchmarks };
+    fs::write(&path, serde_json::to_string_pretty(&results)?)?;
+    eprintln!("wrote {}", path.display());
+    Ok(())
+}
+
+fn criterion_home() -> PathBuf {
+    if let Some(home) = std::env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    let target = std::env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+    Path::new(&target).join("criterion")
+}
+
+fn collect(dir: &Path, started: SystemTime, out: &mut Vec<Measurement>) -> std::io::Result<()> {
+    let new = dir.join("new");
+    let estimates = new.join("estimates.json");
+    if estimates.is_file() && fs::metadata(&estimates)?.modified()? >= started {
+        let benchmark = read_json(&new.join("benchmark.json"))?;
+        let estimates = read_json(&estimates)?;
+
+        let mean_ns = estimates["mean"]["point_estimate"]
+            .as_f64()
+            .unwrap_or(f64::NAN);
+        let elements = benchmark["throughput"]["Elements"].as_f64();
+        out.push(Measurement {
+            id: benchmark["full_id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            mean_ns,
+            elements_per_second: elements.map(|n| n * 1e9 / mean_ns),
+        });
+    }
+
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() && path.file_name() != Some("new".as_ref()) {
+                collect(&path, started, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_json(path: &Path) -> std::io::Result<serde_json::Value> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
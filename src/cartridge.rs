@@ -0,0 +1,373 @@
+//! Cartridges
+//!
+//! ROM images are loaded from iNES or NES 2.0 files. The board is emulated by a `Mapper` deciding
+//! what the CPU sees at $4020-$FFFF and the PPU sees at $0000-$1FFF.
+
+mod bandai;
+mod battery;
+mod eeprom;
+mod fme7;
+mod mmc5;
+mod mmc5_audio;
+mod namco163;
+mod nrom;
+mod opll;
+mod sunsoft5b;
+mod vrc4;
+mod vrc6;
+mod vrc7;
+mod vrc_irq;
+
+use std::fmt;
+use std::hash::Hasher;
+
+use crate::md5::Md5;
+use crate::InitPolicy;
+use bandai::Bandai;
+use fme7::Fme7;
+use mmc5::Mmc5;
+use namco163::Namco163;
+use nrom::Nrom;
+use vrc4::Vrc4;
+use vrc6::Vrc6;
+use vrc7::Vrc7;
+
+pub(crate) use battery::Battery;
+pub use battery::{FileStorage, Storage, StorageClone};
+
+/// Nametable mirroring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+/// iNES header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub mapper: u16,
+    pub submapper: u8,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    // NES 2.0 only, 0 in iNES files
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub mirroring: Mirroring,
+    // battery-backed PRG RAM or other persistent memory
+    pub battery: bool,
+    pub trainer: bool,
+    pub nes2: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidHeader,
+    // the file is shorter than the header says
+    Truncated,
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidHeader => write!(f, "not an iNES file"),
+            Error::Truncated => write!(f, "ROM image is truncated"),
+            Error::UnsupportedMapper(n) => write!(f, "mapper {} is not supported", n),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Cartridge inserted into the console
+#[derive(Clone)]
+pub struct Cartridge {
+    header: Header,
+    // MD5 of PRG and CHR ROM
+    checksum: [u8; 16],
+    mapper: Box<dyn Mapper>,
+}
+
+impl Cartridge {
+    /// Parses an iNES or NES 2.0 file
+    pub fn from_ines(bytes: &[u8]) -> Result<Self, Error> {
+        let rom = Rom::parse(bytes)?;
+        let header = rom.header.clone();
+
+        let mut md5 = Md5::new();
+        md5.update(&rom.prg_rom);
+        md5.update(&rom.chr_rom);
+        let checksum = md5.finish();
+
+        let mapper: Box<dyn Mapper> = match header.mapper {
+            0 => Box::new(Nrom::new(rom)),
+            5 => Box::new(Mmc5::new(rom)),
+            16 | 153 | 159 => Box::new(Bandai::new(rom)),
+            19 => Box::new(Namco163::new(rom)),
+            21 | 22 | 23 | 25 => Box::new(Vrc4::new(rom)),
+            24 | 26 => Box::new(Vrc6::new(rom)),
+            69 => Box::new(Fme7::new(rom)),
+            85 => Box::new(Vrc7::new(rom)),
+            n => return Err(Error::UnsupportedMapper(n)),
+        };
+        Ok(Self {
+            header,
+            checksum,
+            mapper,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// MD5 of PRG and CHR ROM, as FCEUX identifies ROMs
+    pub fn checksum(&self) -> [u8; 16] {
+        self.checksum
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.mirroring()
+    }
+
+    pub fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.mapper.ppu_read(addr)
+    }
+
+    pub fn ppu_write(&mut self, addr: u16, value: u8) {
+        self.mapper.ppu_write(addr, value)
+    }
+
+    /// Reads $2000-$2FFF if the board provides the nametable, otherwise it is in CIRAM
+    pub fn nametable_read(&mut self, addr: u16) -> Option<u8> {
+        self.mapper.nametable_read(addr)
+    }
+
+    /// Writes $2000-$2FFF, returning false if the nametable is in CIRAM
+    pub fn nametable_write(&mut self, addr: u16, value: u8) -> bool {
+        self.mapper.nametable_write(addr, value)
+    }
+
+    pub(crate) fn snoop_ppu_register(&mut self, addr: u16, value: u8) {
+        self.mapper.snoop_ppu_register(addr, value)
+    }
+
+    pub(crate) fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.mapper.cpu_read(addr)
+    }
+
+    pub(crate) fn cpu_write(&mut self, addr: u16, value: u8) {
+        self.mapper.cpu_write(addr, value)
+    }
+
+    pub(crate) fn cpu_peek(&self, addr: u16) -> u8 {
+        self.mapper.cpu_peek(addr)
+    }
+
+    pub(crate) fn on_cpu_tick(&mut self) {
+        self.mapper.on_cpu_tick()
+    }
+
+    pub(crate) fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+
+    /// Level of the expansion audio, from 0 to the full scale of the chip at 1, for the APU to
+    /// mix with its own channels
+    pub fn audio_output(&self) -> f32 {
+        self.mapper.audio_output()
+    }
+
+    /// Battery-backed memory in the layout of `.sav` files, if the cartridge has a battery
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.header.battery.then(|| self.mapper.save_data())
+    }
+
+    /// Restores battery-backed memory from a `.sav` file. Shorter saves fill the start only.
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.mapper.load_save_data(data);
+    }
+
+    // Restores the board's power-up state. RAM is filled per policy, PRG RAM from `prg_ram_stream`
+    // and the board's own memories from `board_stream`, but battery-backed memory keeps its
+    // contents.
+    pub(crate) fn power_on(&mut self, policy: InitPolicy, prg_ram_stream: u64, board_stream: u64) {
+        let saved = self.header.battery.then(|| self.mapper.save_data());
+        policy.fill(self.mapper.prg_ram_mut(), prg_ram_stream);
+        self.mapper.power_on(policy, board_stream);
+        if let Some(data) = saved {
+            self.mapper.load_save_data(&data);
+        }
+    }
+
+    pub(crate) fn hash_state(&self, state: &mut dyn Hasher) {
+        state.write(&self.checksum);
+        self.mapper.hash_state(state);
+    }
+}
+
+/// Contents of an iNES file
+struct Rom {
+    header: Header,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+}
+
+impl Rom {
+    // https://wiki.nesdev.org/w/index.php?title=INES
+    // https://wiki.nesdev.org/w/index.php?title=NES_2.0
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 16 || bytes[0..4] != *b"NES\x1A" {
+            return Err(Error::InvalidHeader);
+        }
+        let flags6 = bytes[6];
+        let flags7 = bytes[7];
+        let nes2 = flags7 & 0x0C == 0x08;
+
+        let mut mapper = ((flags7 & 0xF0) | (flags6 >> 4)) as u16;
+        let mut submapper = 0;
+        let mut prg_rom_units = bytes[4] as usize;
+        let mut chr_rom_units = bytes[5] as usize;
+        let mut prg_ram_size = 0;
+        let mut prg_nvram_size = 0;
+        if nes2 {
+            mapper |= ((bytes[8] & 0x0F) as u16) << 8;
+            submapper = bytes[8] >> 4;
+            prg_rom_units |= ((bytes[9] & 0x0F) as usize) << 8;
+            chr_rom_units |= ((bytes[9] >> 4) as usize) << 8;
+            // shift counts of 64 bytes, 0 for none
+            let ram_size = |shift: u8| if shift == 0 { 0 } else { 64 << shift };
+            prg_ram_size = ram_size(bytes[10] & 0x0F);
+            prg_nvram_size = ram_size(bytes[10] >> 4);
+        }
+
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let header = Header {
+            mapper,
+            submapper,
+            prg_rom_size: prg_rom_units * 0x4000,
+            chr_rom_size: chr_rom_units * 0x2000,
+            prg_ram_size,
+            prg_nvram_size,
+            mirroring,
+            battery: flags6 & 0x02 != 0,
+            trainer: flags6 & 0x04 != 0,
+            nes2,
+        };
+
+        let prg_start = if header.trainer { 16 + 512 } else { 16 };
+        let chr_start = prg_start + header.prg_rom_size;
+        let chr_end = chr_start + header.chr_rom_size;
+        if bytes.len() < chr_end {
+            return Err(Error::Truncated);
+        }
+        Ok(Self {
+            prg_rom: bytes[prg_start..chr_start].to_vec(),
+            chr_rom: bytes[chr_start..chr_end].to_vec(),
+            header,
+        })
+    }
+
+    // 8KB of CHR RAM if the cartridge has no CHR ROM
+    fn chr(&self) -> (Vec<u8>, bool) {
+        if self.chr_rom.is_empty() {
+            (vec![0; 0x2000], true)
+        } else {
+            (self.chr_rom.clone(), false)
+        }
+    }
+}
+
+/// Cartridge board
+pub(crate) trait Mapper: MapperClone {
+    /// Reads $4020-$FFFF without side effects
+    fn cpu_peek(&self, addr: u16) -> u8;
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.cpu_peek(addr)
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8);
+
+    fn ppu_read(&mut self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, value: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    /// Nametable fetches, for boards watching them or mapping their own memory there
+    fn nametable_read(&mut self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    fn nametable_write(&mut self, _addr: u16, _value: u8) -> bool {
+        false
+    }
+
+    /// Called on CPU writes to the PPU registers, for boards listening to the bus
+    fn snoop_ppu_register(&mut self, _addr: u16, _value: u8) {}
+
+    /// Called every CPU cycle, for boards counting cycles
+    fn on_cpu_tick(&mut self) {}
+
+    /// Whether the board asserts IRQ
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Expansion audio, from 0 to 1
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    /// Restores the registers to their power-up state and fills memories other than PRG RAM, such
+    /// as CHR RAM, per `policy`
+    fn power_on(&mut self, _policy: InitPolicy, _stream: u64) {}
+
+    /// PRG RAM, usually at $6000-$7FFF
+    fn prg_ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+
+    /// Battery-backed memory, for boards keeping more than PRG RAM
+    fn save_data(&self) -> Vec<u8> {
+        self.prg_ram().to_vec()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let ram = self.prg_ram_mut();
+        let len = data.len().min(ram.len());
+        ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Hashes everything that can change while running: RAM, registers, CHR RAM
+    fn hash_state(&self, state: &mut dyn Hasher);
+}
+
+pub(crate) trait MapperClone {
+    fn clone_box(&self) -> Box<dyn Mapper>;
+}
+
+impl<T: Mapper + Clone + 'static> MapperClone for T {
+    fn clone_box(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
@@ -0,0 +1,272 @@
+use std::hash::{Hash, Hasher};
+
+use super::eeprom::{Chip, Eeprom};
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_016
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_153
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_159
+//
+// Bandai FCG-1/2 and LZ93D50 boards: 16KB PRG bank at $8000 with the last bank at $C000, eight
+// 1KB CHR banks and a 16-bit IRQ counter decremented every CPU cycle. Saves are kept in a serial
+// EEPROM (mappers 16 and 159) or in 8KB PRG RAM (mapper 153).
+#[derive(Clone)]
+pub(super) struct Bandai {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    board: Board,
+    eeprom: Option<Eeprom>,
+    // mapper 153 only
+    prg_ram: Vec<u8>,
+    prg_ram_enabled: bool,
+
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    mirroring: Mirroring,
+
+    irq_enabled: bool,
+    irq_counter: u16,
+    // LZ93D50 only: $B and $C write a latch copied to the counter by $A
+    irq_latch: u16,
+    irq: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Board {
+    // FCG-1/2: registers at $6000-$7FFF, written straight to the IRQ counter
+    Fcg,
+    // LZ93D50: registers at $8000-$FFFF
+    Lz93d50,
+    // iNES mapper 16 without a submapper could be either, so registers are at both
+    Either,
+}
+
+impl Bandai {
+    pub(super) fn new(rom: Rom) -> Self {
+        let header = &rom.header;
+        let (board, eeprom) = match (header.mapper, header.submapper) {
+            (16, 4) => (Board::Fcg, None),
+            (16, 5) => (Board::Lz93d50, Some(Eeprom::new(Chip::C24C02))),
+            (16, _) => (Board::Either, Some(Eeprom::new(Chip::C24C02))),
+            (159, _) => (Board::Lz93d50, Some(Eeprom::new(Chip::X24C01))),
+            _ => (Board::Lz93d50, None),
+        };
+        let prg_ram = if header.mapper == 153 {
+            vec![0; 0x2000]
+        } else {
+            vec![]
+        };
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            chr,
+            chr_ram,
+            board,
+            eeprom,
+            prg_ram,
+            prg_ram_enabled: false,
+            chr_banks: [0; 8],
+            prg_bank: 0,
+            // $x9 powers up as 0
+            mirroring: Mirroring::Vertical,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq: false,
+        }
+    }
+
+    fn is_register(&self, addr: u16) -> bool {
+        match addr {
+            0x6000..=0x7FFF => self.board != Board::Lz93d50,
+            0x8000..=0xFFFF => self.board != Board::Fcg,
+            _ => false,
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        // on mapper 153 bit 0 of the CHR registers selects the 256KB half of PRG ROM
+        let outer = if self.prg_ram.is_empty() {
+            0
+        } else {
+            (self.chr_banks[..4].iter().fold(0, |b, r| b | r) & 1) as usize
+        };
+        let bank = match addr {
+            0x8000..=0xBFFF => (self.prg_bank & 0x0F) as usize,
+            _ => 0x0F,
+        };
+        ((outer << 4 | bank) * 0x4000 + (addr & 0x3FFF) as usize) % self.prg_rom.len()
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let offset = if self.chr_ram {
+            addr as usize
+        } else {
+            self.chr_banks[(addr >> 10) as usize] as usize * 0x400 + (addr & 0x3FF) as usize
+        };
+        offset % self.chr.len()
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr & 0x0F {
+            n @ 0x0..=0x7 => self.chr_banks[n as usize] = value,
+            0x8 => self.prg_bank = value,
+            0x9 => {
+                self.mirroring = match value & 3 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenLower,
+                    _ => Mirroring::SingleScreenUpper,
+                }
+            }
+            0xA => {
+                self.irq_enabled = value & 1 == 1;
+                if self.board != Board::Fcg {
+                    self.irq_counter = self.irq_latch;
+                }
+                self.irq = false;
+            }
+            0xB => self.set_irq_low(value),
+            0xC => self.set_irq_high(value),
+            0xD => {
+                self.prg_ram_enabled = value & 0x20 != 0;
+                if let Some(eeprom) = self.eeprom.as_mut() {
+                    eeprom.write(value & 0x20 != 0, value & 0x40 != 0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_irq_low(&mut self, value: u8) {
+        let target = match self.board {
+            Board::Fcg => &mut self.irq_counter,
+            _ => &mut self.irq_latch,
+        };
+        *target = (*target & 0xFF00) | value as u16;
+    }
+
+    fn set_irq_high(&mut self, value: u8) {
+        let target = match self.board {
+            Board::Fcg => &mut self.irq_counter,
+            _ => &mut self.irq_latch,
+        };
+        *target = (*target & 0x00FF) | (value as u16) << 8;
+    }
+}
+
+impl Mapper for Bandai {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if !self.prg_ram.is_empty() && self.prg_ram_enabled => {
+                self.prg_ram[(addr - 0x6000) as usize]
+            }
+            0x6000..=0x7FFF if !self.prg_ram.is_empty() => 0,
+            // SDA of the EEPROM on bit 4
+            0x6000..=0x7FFF => match &self.eeprom {
+                Some(eeprom) => (eeprom.sda() as u8) << 4,
+                None => 0,
+            },
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if self.is_register(addr) {
+            self.write_register(addr, value);
+        } else if let 0x6000..=0x7FFF = addr {
+            if self.prg_ram_enabled && !self.prg_ram.is_empty() {
+                self.prg_ram[(addr - 0x6000) as usize] = value;
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn on_cpu_tick(&mut self) {
+        if self.irq_enabled {
+            // the counter is checked before it's decremented
+            if self.irq_counter == 0 {
+                self.irq = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        if let Some(eeprom) = self.eeprom.as_mut() {
+            eeprom.power_on();
+        }
+        self.prg_ram_enabled = false;
+        self.chr_banks = [0; 8];
+        self.prg_bank = 0;
+        self.mirroring = Mirroring::Vertical;
+        self.irq_enabled = false;
+        self.irq_counter = 0;
+        self.irq_latch = 0;
+        self.irq = false;
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn save_data(&self) -> Vec<u8> {
+        match &self.eeprom {
+            Some(eeprom) => eeprom.data().to_vec(),
+            None => self.prg_ram.clone(),
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let memory = match self.eeprom.as_mut() {
+            Some(eeprom) => eeprom.data_mut(),
+            None => &mut self.prg_ram,
+        };
+        let len = data.len().min(memory.len());
+        memory[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.eeprom.hash(&mut state);
+        self.prg_ram.hash(&mut state);
+        self.prg_ram_enabled.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.prg_bank.hash(&mut state);
+        self.mirroring.hash(&mut state);
+        self.irq_enabled.hash(&mut state);
+        self.irq_counter.hash(&mut state);
+        self.irq_latch.hash(&mut state);
+        self.irq.hash(&mut state);
+    }
+}
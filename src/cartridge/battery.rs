@@ -0,0 +1,101 @@
+// Battery-backed memory kept between sessions
+//
+// Save files hold the memory as is, like the .sav files of other emulators: 8KB of PRG RAM for
+// most boards.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::Cartridge;
+
+/// Where a cartridge's battery-backed memory is kept while the console is off
+///
+/// Hosts implement this to keep saves somewhere else than files.
+pub trait Storage: StorageClone {
+    /// Reads saved memory, or `None` if nothing was saved yet
+    fn load(&mut self) -> io::Result<Option<Vec<u8>>>;
+
+    fn save(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+pub trait StorageClone {
+    fn clone_box(&self) -> Box<dyn Storage>;
+}
+
+impl<T: Storage + Clone + 'static> StorageClone for T {
+    fn clone_box(&self) -> Box<dyn Storage> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Storage> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// `.sav` file
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&mut self, data: &[u8]) -> io::Result<()> {
+        // written aside first, so a crash doesn't leave half a save
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Storage of the inserted cartridge, with what was last saved to skip unchanged saves
+#[derive(Clone)]
+pub(crate) struct Battery {
+    storage: Box<dyn Storage>,
+    saved: Vec<u8>,
+}
+
+impl Battery {
+    /// Loads the save into `cartridge` if it has a battery
+    pub(crate) fn load(
+        mut storage: Box<dyn Storage>,
+        cartridge: &mut Cartridge,
+    ) -> io::Result<Self> {
+        if cartridge.header().battery {
+            if let Some(data) = storage.load()? {
+                cartridge.load_save_data(&data);
+            }
+        }
+        Ok(Self {
+            storage,
+            saved: cartridge.save_data().unwrap_or_default(),
+        })
+    }
+
+    pub(crate) fn flush(&mut self, cartridge: &Cartridge) -> io::Result<()> {
+        match cartridge.save_data() {
+            Some(data) if data != self.saved => {
+                self.storage.save(&data)?;
+                self.saved = data;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
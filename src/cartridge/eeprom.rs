@@ -0,0 +1,233 @@
+// I²C serial EEPROMs of Bandai boards
+// https://wiki.nesdev.org/w/index.php?title=Bandai_FCG_board#Serial_EEPROM
+//
+// The board drives SCL and SDA from a register and reads SDA back. A start condition (SDA falling
+// while SCL is high) begins a transfer, a stop condition (SDA rising while SCL is high) ends it,
+// and every other bit is sampled on the rising edge of SCL.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Chip {
+    /// Xicor X24C01, 128 bytes. The first byte after a start is the word address, bits sent least
+    /// significant first, with the read/write bit last.
+    X24C01,
+    /// 24C02, 256 bytes. A device address then the word address, most significant bit first.
+    C24C02,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Mode {
+    Idle,
+    // receiving the device address of a 24C02
+    Device,
+    // receiving the word address
+    Address,
+    Read,
+    Write,
+    // pulling SDA low through the 9th clock
+    Ack,
+    // waiting for the master to acknowledge a byte read
+    MasterAck,
+}
+
+#[derive(Clone, Hash)]
+pub(super) struct Eeprom {
+    chip: Chip,
+    data: Vec<u8>,
+
+    mode: Mode,
+    // mode after the acknowledge
+    next: Mode,
+    address: u8,
+    // byte being shifted in or out, and the number of bits shifted
+    shift: u8,
+    bits: u8,
+    // SDA as driven by the chip, released high
+    output: bool,
+
+    scl: bool,
+    sda: bool,
+}
+
+impl Eeprom {
+    pub(super) fn new(chip: Chip) -> Self {
+        let size = match chip {
+            Chip::X24C01 => 128,
+            Chip::C24C02 => 256,
+        };
+        Self {
+            chip,
+            // erased
+            data: vec![0xFF; size],
+            mode: Mode::Idle,
+            next: Mode::Idle,
+            address: 0,
+            shift: 0,
+            bits: 0,
+            output: true,
+            scl: false,
+            sda: false,
+        }
+    }
+
+    // The contents are kept, a transfer in progress is lost
+    pub(super) fn power_on(&mut self) {
+        *self = Self {
+            data: std::mem::take(&mut self.data),
+            ..Self::new(self.chip)
+        };
+    }
+
+    pub(super) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub(super) fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// SDA as seen by the board: low if either side pulls it low
+    pub(super) fn sda(&self) -> bool {
+        self.output && self.sda
+    }
+
+    pub(super) fn write(&mut self, scl: bool, sda: bool) {
+        if self.scl && scl && self.sda && !sda {
+            self.start();
+        } else if self.scl && scl && !self.sda && sda {
+            self.mode = Mode::Idle;
+            self.output = true;
+        } else if !self.scl && scl {
+            self.rise(sda);
+        } else if self.scl && !scl {
+            self.fall();
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn start(&mut self) {
+        self.mode = match self.chip {
+            Chip::X24C01 => Mode::Address,
+            Chip::C24C02 => Mode::Device,
+        };
+        self.shift = 0;
+        self.bits = 0;
+        self.output = true;
+    }
+
+    fn shift_in(&mut self, bit: bool) {
+        if self.bits < 8 {
+            match self.chip {
+                Chip::X24C01 => self.shift |= (bit as u8) << self.bits,
+                Chip::C24C02 => self.shift = self.shift << 1 | bit as u8,
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn shift_out(&mut self) {
+        if self.bits < 8 {
+            let bit = match self.chip {
+                Chip::X24C01 => self.shift >> self.bits & 1,
+                Chip::C24C02 => self.shift >> (7 - self.bits) & 1,
+            };
+            self.output = bit == 1;
+            self.bits += 1;
+        }
+    }
+
+    fn mask(&self) -> u8 {
+        (self.data.len() - 1) as u8
+    }
+
+    // SCL rising: the chip samples SDA
+    fn rise(&mut self, sda: bool) {
+        match self.mode {
+            Mode::Device | Mode::Address | Mode::Write => self.shift_in(sda),
+            Mode::Read => self.shift_out(),
+            Mode::MasterAck => {
+                self.next = if sda { Mode::Idle } else { Mode::Read };
+            }
+            Mode::Idle | Mode::Ack => {}
+        }
+    }
+
+    // SCL falling: the chip changes SDA
+    fn fall(&mut self) {
+        if self.mode == Mode::Ack {
+            self.output = true;
+            self.mode = self.next;
+            self.bits = 0;
+            self.shift = 0;
+            if self.mode == Mode::Read {
+                self.shift = self.data[self.address as usize];
+            }
+            return;
+        }
+        if self.mode == Mode::MasterAck {
+            self.mode = self.next;
+            self.bits = 0;
+            self.shift = self.data[self.address as usize];
+            return;
+        }
+        if self.bits != 8 {
+            return;
+        }
+
+        let byte = self.shift;
+        match self.mode {
+            Mode::Device => {
+                // 1010 A2 A1 A0 R/W
+                if byte & 0xF0 != 0xA0 {
+                    self.mode = Mode::Idle;
+                    return;
+                }
+                self.next = if byte & 1 == 1 {
+                    Mode::Read
+                } else {
+                    Mode::Address
+                };
+                self.mode = Mode::Ack;
+            }
+            Mode::Address => match self.chip {
+                Chip::X24C01 => {
+                    self.address = byte & 0x7F;
+                    self.next = if byte & 0x80 != 0 {
+                        Mode::Read
+                    } else {
+                        Mode::Write
+                    };
+                    self.mode = Mode::Ack;
+                }
+                Chip::C24C02 => {
+                    self.address = byte;
+                    self.next = Mode::Write;
+                    self.mode = Mode::Ack;
+                }
+            },
+            Mode::Write => {
+                self.data[self.address as usize] = byte;
+                // writes wrap around within a page: 4 bytes on the X24C01, 8 on the 24C02
+                let page = match self.chip {
+                    Chip::X24C01 => 3,
+                    Chip::C24C02 => 7,
+                };
+                self.address = (self.address & !page) | (self.address.wrapping_add(1) & page);
+                self.address &= self.mask();
+                self.next = Mode::Write;
+                self.mode = Mode::Ack;
+            }
+            Mode::Read => {
+                self.output = true;
+                self.address = self.address.wrapping_add(1) & self.mask();
+                self.mode = Mode::MasterAck;
+            }
+            _ => {}
+        }
+        if self.mode == Mode::Ack {
+            self.output = false;
+        }
+        self.bits = 0;
+        self.shift = 0;
+    }
+}
@@ -0,0 +1,201 @@
+use std::hash::{Hash, Hasher};
+
+use super::sunsoft5b::Sunsoft5b;
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=Sunsoft_FME-7
+//
+// Sunsoft FME-7 and 5B: a command register at $8000 and its parameter at $A000 set eight 1KB CHR
+// banks, three 8KB PRG banks with the last fixed, a bank of ROM or RAM at $6000, the mirroring
+// and a 16-bit IRQ counter decremented every CPU cycle. The 5B adds its sound at $C000/$E000.
+#[derive(Clone)]
+pub(super) struct Fme7 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    command: u8,
+    chr_banks: [u8; 8],
+    // command 8: RAM enable, RAM select, bank
+    prg_6000: u8,
+    prg_banks: [u8; 3],
+    mirroring: u8,
+
+    irq_enabled: bool,
+    counter_enabled: bool,
+    irq_counter: u16,
+    irq: bool,
+
+    audio: Sunsoft5b,
+}
+
+impl Fme7 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; 0x2000],
+            chr,
+            chr_ram,
+            command: 0,
+            chr_banks: [0; 8],
+            prg_6000: 0,
+            prg_banks: [0; 3],
+            mirroring: 0,
+            irq_enabled: false,
+            counter_enabled: false,
+            irq_counter: 0,
+            irq: false,
+            audio: Sunsoft5b::new(),
+        }
+    }
+
+    fn ram_selected(&self) -> bool {
+        self.prg_6000 & 0x40 != 0
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.prg_6000 & 0xC0 == 0xC0
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x6000..=0x7FFF => (self.prg_6000 & 0x3F) as usize,
+            0x8000..=0xDFFF => self.prg_banks[((addr - 0x8000) / 0x2000) as usize] as usize,
+            _ => self.prg_rom.len() / 0x2000 - 1,
+        };
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_rom.len()
+    }
+
+    fn ram_addr(&self, addr: u16) -> usize {
+        ((self.prg_6000 & 0x3F) as usize * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_ram.len()
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr >> 10) as usize & 7] as usize;
+        (bank * 0x400 + (addr & 0x3FF) as usize) % self.chr.len()
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            n @ 0x0..=0x7 => self.chr_banks[n as usize] = value,
+            0x8 => self.prg_6000 = value,
+            n @ 0x9..=0xB => self.prg_banks[(n - 0x9) as usize] = value & 0x3F,
+            0xC => self.mirroring = value & 3,
+            0xD => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.counter_enabled = value & 0x80 != 0;
+                self.irq = false;
+            }
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | (value as u16) << 8,
+        }
+    }
+}
+
+impl Mapper for Fme7 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => self.prg_ram[self.ram_addr(addr)],
+            // RAM selected but disabled: open bus
+            0x6000..=0x7FFF if self.ram_selected() => 0,
+            0x6000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => {
+                let addr = self.ram_addr(addr);
+                self.prg_ram[addr] = value;
+            }
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio.select(value),
+            0xE000..=0xFFFF => self.audio.write(value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        if self.counter_enabled {
+            // the IRQ fires as the counter wraps from $0000 to $FFFF
+            if self.irq_counter == 0 && self.irq_enabled {
+                self.irq = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+        self.audio.tick();
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        self.command = 0;
+        self.chr_banks = [0; 8];
+        self.prg_6000 = 0;
+        self.prg_banks = [0; 3];
+        self.mirroring = 0;
+        self.irq_enabled = false;
+        self.counter_enabled = false;
+        self.irq_counter = 0;
+        self.irq = false;
+        self.audio = Sunsoft5b::new();
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.command.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.prg_6000.hash(&mut state);
+        self.prg_banks.hash(&mut state);
+        self.mirroring.hash(&mut state);
+        self.irq_enabled.hash(&mut state);
+        self.counter_enabled.hash(&mut state);
+        self.irq_counter.hash(&mut state);
+        self.irq.hash(&mut state);
+        self.audio.hash(&mut state);
+    }
+}
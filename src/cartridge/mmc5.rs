@@ -0,0 +1,621 @@
+use std::hash::{Hash, Hasher};
+
+use super::mmc5_audio::Mmc5Audio;
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=MMC5
+//
+// Most of what the MMC5 does for the picture comes from watching the PPU: it counts scanlines by
+// spotting the three identical nametable fetches that end every line, tells background from sprite
+// fetches by their position in the line, and answers nametable fetches itself with ExRAM or fill
+// data. It also snoops $2000 and $2001 for the sprite size and whether rendering is on.
+//
+// Its sound is two pulse channels and an 8-bit PCM channel, written at $5011 or read from
+// $8000-$BFFF as the CPU fetches it.
+#[derive(Clone)]
+pub(super) struct Mmc5 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    exram: Vec<u8>,
+
+    prg_mode: u8,
+    // $5113-$5117
+    prg_banks: [u8; 5],
+    prg_ram_protect: [u8; 2],
+
+    chr_mode: u8,
+    // $5120-$5127, for sprites and everything with 8x8 sprites
+    chr_a: [u16; 8],
+    // $5128-$512B, for the background with 8x16 sprites
+    chr_b: [u16; 4],
+    chr_upper: u8,
+    last_chr_b: bool,
+
+    exram_mode: u8,
+    nametables: u8,
+    fill_tile: u8,
+    fill_color: u8,
+
+    split: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    multiplicand: u8,
+    multiplier: u8,
+
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    pcm_irq: bool,
+    pcm: u8,
+    audio: Mmc5Audio,
+
+    // snooped from $2000 and $2001
+    large_sprites: bool,
+    rendering: bool,
+
+    // scanline detection
+    last_read: u16,
+    same_reads: u8,
+    // PPU reads since the line started, and CPU cycles since the last one
+    fetches: u16,
+    idle_cycles: u8,
+    in_frame: bool,
+    scanline: u8,
+    irq_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // ExRAM byte of the tile being fetched, for extended attributes and the split
+    tile: Tile,
+}
+
+#[derive(Debug, Default, Clone, Copy, Hash)]
+enum Tile {
+    #[default]
+    Normal,
+    // extended attribute byte
+    Extended(u8),
+    // line of the split region
+    Split(u8),
+}
+
+impl Mmc5 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let (chr, chr_ram) = rom.chr();
+        let prg_ram = vec![0; prg_ram_size(&rom)];
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram,
+            chr,
+            chr_ram,
+            exram: vec![0; 0x400],
+            prg_mode: 3,
+            prg_banks: [0, 0, 0, 0, 0xFF],
+            prg_ram_protect: [0, 0],
+            chr_mode: 0,
+            chr_a: [0; 8],
+            chr_b: [0; 4],
+            chr_upper: 0,
+            last_chr_b: false,
+            exram_mode: 0,
+            nametables: 0,
+            fill_tile: 0,
+            fill_color: 0,
+            split: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            pcm_read_mode: false,
+            pcm_irq_enabled: false,
+            pcm_irq: false,
+            pcm: 0,
+            audio: Mmc5Audio::new(),
+            large_sprites: false,
+            rendering: false,
+            last_read: 0,
+            same_reads: 0,
+            fetches: 0,
+            idle_cycles: 0,
+            in_frame: false,
+            scanline: 0,
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            tile: Tile::Normal,
+        }
+    }
+
+    // Bank register and offset in 8KB units of $6000-$FFFF. Bit 7 of the register selects ROM.
+    fn prg_bank(&self, addr: u16) -> (u8, usize) {
+        let slot = ((addr - 0x6000) / 0x2000) as usize;
+        let offset = (addr & 0x1FFF) as usize;
+        if slot == 0 {
+            return (self.prg_banks[0] & 0x7F, offset);
+        }
+        // register and the number of 8KB banks it selects
+        let (reg, size) = match (self.prg_mode, slot) {
+            (0, _) => (4, 4),
+            (1, 1 | 2) => (2, 2),
+            (1, _) => (4, 2),
+            (2, 1 | 2) => (2, 2),
+            (2, 3) => (3, 1),
+            (2, _) => (4, 1),
+            (_, n) => (n, 1),
+        };
+        let first = match size {
+            4 => 1,
+            2 if slot <= 2 => 1,
+            2 => 3,
+            _ => slot,
+        };
+        let value = self.prg_banks[reg];
+        let bank = (value & !(size as u8 - 1)) + (slot - first) as u8;
+        // ROM flag kept in bit 7, $5117 is always ROM
+        let rom = reg == 4 || value & 0x80 != 0;
+        ((bank & 0x7F) | (rom as u8) << 7, offset)
+    }
+
+    // Offset in PRG RAM of an 8KB bank and offset
+    fn ram_addr(&self, bank: u8, offset: usize) -> usize {
+        let bank = match self.prg_ram.len() {
+            // two 8KB chips, picked by bit 2
+            0x4000 => (bank >> 2 & 1) as usize,
+            len => (bank & 0x07) as usize % (len / 0x2000).max(1),
+        };
+        (bank * 0x2000 + offset) % self.prg_ram.len()
+    }
+
+    fn prg_read(&self, addr: u16) -> u8 {
+        let (bank, offset) = self.prg_bank(addr);
+        let slot = (addr - 0x6000) / 0x2000;
+        if slot != 0 && bank & 0x80 != 0 {
+            if self.prg_rom.is_empty() {
+                return 0;
+            }
+            self.prg_rom[((bank & 0x7F) as usize * 0x2000 + offset) % self.prg_rom.len()]
+        } else if self.prg_ram.is_empty() {
+            0
+        } else {
+            self.prg_ram[self.ram_addr(bank, offset)]
+        }
+    }
+
+    fn prg_write(&mut self, addr: u16, value: u8) {
+        if self.prg_ram_protect != [2, 1] || self.prg_ram.is_empty() {
+            return;
+        }
+        let (bank, offset) = self.prg_bank(addr);
+        let slot = (addr - 0x6000) / 0x2000;
+        if slot == 0 || bank & 0x80 == 0 {
+            let addr = self.ram_addr(bank, offset);
+            self.prg_ram[addr] = value;
+        }
+    }
+
+    // Offset in CHR of a pattern fetch
+    fn chr_addr(&self, addr: u16) -> usize {
+        let addr = addr & 0x1FFF;
+        let sprite = (128..160).contains(&self.fetches);
+        let use_b = if self.large_sprites && self.in_frame {
+            !sprite
+        } else {
+            self.last_chr_b
+        };
+
+        let bank_offset = |size: u16, bank: u16| bank as usize * size as usize;
+        let (bank, offset) = match (self.chr_mode, use_b) {
+            (0, false) => (bank_offset(0x2000, self.chr_a[7]), addr & 0x1FFF),
+            (1, false) => (
+                bank_offset(0x1000, self.chr_a[(addr >> 12) as usize * 4 + 3]),
+                addr & 0x0FFF,
+            ),
+            (2, false) => (
+                bank_offset(0x800, self.chr_a[(addr >> 11) as usize * 2 + 1]),
+                addr & 0x07FF,
+            ),
+            (_, false) => (
+                bank_offset(0x400, self.chr_a[(addr >> 10) as usize]),
+                addr & 0x03FF,
+            ),
+            (0, true) => (bank_offset(0x2000, self.chr_b[3]), addr & 0x1FFF),
+            (1, true) => (bank_offset(0x1000, self.chr_b[3]), addr & 0x0FFF),
+            (2, true) => (
+                bank_offset(0x800, self.chr_b[((addr >> 11) & 1) as usize * 2 + 1]),
+                addr & 0x07FF,
+            ),
+            (_, true) => (
+                bank_offset(0x400, self.chr_b[((addr >> 10) & 3) as usize]),
+                addr & 0x03FF,
+            ),
+        };
+        (bank + offset as usize) % self.chr.len()
+    }
+
+    // Line of the frame a background tile is fetched for, and its column
+    fn tile_position(&self) -> Option<(u8, u8)> {
+        match self.fetches {
+            0..=127 => Some((self.scanline, (2 + self.fetches / 4) as u8)),
+            // prefetch of the first two tiles of the next line
+            160..=167 => Some((
+                self.scanline.wrapping_add(1),
+                ((self.fetches - 160) / 4) as u8,
+            )),
+            // sprites and dummy fetches
+            _ => None,
+        }
+    }
+
+    // Line and column of the split region the tile being fetched is in
+    fn split_position(&self) -> Option<(u8, u8)> {
+        if self.split & 0x80 == 0 || self.exram_mode >= 2 || !self.in_frame {
+            return None;
+        }
+        let (line, column) = self.tile_position()?;
+        let threshold = self.split & 0x1F;
+        let inside = if self.split & 0x40 != 0 {
+            column >= threshold
+        } else {
+            column < threshold
+        };
+        inside.then_some((line, column))
+    }
+
+    // Watches every PPU read to follow the rendering
+    fn on_ppu_read(&mut self, addr: u16) {
+        self.idle_cycles = 0;
+        if (0x2000..=0x2FFF).contains(&addr) && addr == self.last_read {
+            self.same_reads = self.same_reads.saturating_add(1);
+        } else {
+            self.same_reads = 0;
+        }
+        self.last_read = addr;
+
+        // the 3rd identical nametable fetch is the first one of a line
+        if self.same_reads == 2 {
+            if self.in_frame {
+                self.scanline = self.scanline.wrapping_add(1);
+                if self.scanline == self.irq_compare {
+                    self.irq_pending = true;
+                }
+            } else {
+                self.in_frame = true;
+                self.scanline = 0;
+            }
+            self.fetches = 0;
+        } else {
+            self.fetches = self.fetches.saturating_add(1);
+        }
+    }
+
+    fn nametable_source(&self, addr: u16) -> u8 {
+        (self.nametables >> (((addr >> 10) & 3) * 2)) & 3
+    }
+
+    fn read_register(&mut self, addr: u16) -> Option<u8> {
+        let v = self.peek_register(addr)?;
+        match addr {
+            0x5010 => self.pcm_irq = false,
+            0x5204 => self.irq_pending = false,
+            _ => {}
+        }
+        Some(v)
+    }
+
+    fn peek_register(&self, addr: u16) -> Option<u8> {
+        let v = match addr {
+            0x5010 => (self.pcm_irq as u8) << 7,
+            0x5015 => self.audio.status(),
+            0x5204 => (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6,
+            0x5205 => (self.multiplicand as u16 * self.multiplier as u16) as u8,
+            0x5206 => ((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8,
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => self.exram[(addr - 0x5C00) as usize],
+            0x6000..=0xFFFF => self.prg_read(addr),
+            _ => return None,
+        };
+        Some(v)
+    }
+
+    fn write_chr_register(&mut self, addr: u16, value: u8) {
+        let bank = (self.chr_upper as u16) << 8 | value as u16;
+        match addr {
+            0x5120..=0x5127 => {
+                self.chr_a[(addr - 0x5120) as usize] = bank;
+                self.last_chr_b = false;
+            }
+            _ => {
+                self.chr_b[(addr - 0x5128) as usize] = bank;
+                self.last_chr_b = true;
+            }
+        }
+    }
+}
+
+impl Mapper for Mmc5 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        self.peek_register(addr).unwrap_or(0)
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            // the NMI vector fetch ends the frame
+            0xFFFA | 0xFFFB => {
+                self.in_frame = false;
+                self.irq_pending = false;
+            }
+            0x8000..=0xBFFF if self.pcm_read_mode => {
+                let v = self.prg_read(addr);
+                if v == 0 {
+                    self.pcm_irq = self.pcm_irq_enabled;
+                } else {
+                    self.pcm = v;
+                }
+            }
+            _ => {}
+        }
+        self.read_register(addr).unwrap_or(0)
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5007 | 0x5015 => self.audio.write(addr, value),
+            0x5010 => {
+                self.pcm_read_mode = value & 0x01 != 0;
+                self.pcm_irq_enabled = value & 0x80 != 0;
+            }
+            0x5011 if !self.pcm_read_mode && value != 0 => self.pcm = value,
+            0x5100 => self.prg_mode = value & 3,
+            0x5101 => self.chr_mode = value & 3,
+            0x5102 | 0x5103 => self.prg_ram_protect[(addr - 0x5102) as usize] = value & 3,
+            0x5104 => self.exram_mode = value & 3,
+            0x5105 => self.nametables = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_color = value & 3,
+            0x5113..=0x5117 => self.prg_banks[(addr - 0x5113) as usize] = value,
+            0x5120..=0x512B => self.write_chr_register(addr, value),
+            0x5130 => self.chr_upper = value & 3,
+            0x5200 => self.split = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value,
+            0x5203 => self.irq_compare = value,
+            0x5204 => self.irq_enabled = value & 0x80 != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF => {
+                let i = (addr - 0x5C00) as usize;
+                match self.exram_mode {
+                    // used by the PPU, written as 0 outside rendering
+                    0 | 1 => self.exram[i] = if self.in_frame { value } else { 0 },
+                    2 => self.exram[i] = value,
+                    _ => {}
+                }
+            }
+            0x6000..=0xFFFF => self.prg_write(addr, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.on_ppu_read(addr);
+        match self.tile {
+            Tile::Split(y) if !(128..160).contains(&self.fetches) => {
+                let tile = addr & 0x0FF0;
+                let addr = self.split_bank as usize * 0x1000
+                    + (tile | (addr & 8) | (y & 7) as u16) as usize;
+                self.chr[addr % self.chr.len()]
+            }
+            Tile::Extended(ex) if !(128..160).contains(&self.fetches) => {
+                let bank = (self.chr_upper as usize) << 6 | (ex & 0x3F) as usize;
+                self.chr[(bank * 0x1000 + (addr & 0x0FFF) as usize) % self.chr.len()]
+            }
+            _ => self.chr[self.chr_addr(addr)],
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn nametable_read(&mut self, addr: u16) -> Option<u8> {
+        self.on_ppu_read(addr);
+        let offset = (addr & 0x3FF) as usize;
+        let attribute = offset >= 0x3C0;
+
+        if let Some((line, column)) = self.split_position() {
+            let y = ((self.split_scroll as u16 + line as u16) % 240) as u8;
+            self.tile = Tile::Split(y);
+            let v = if attribute {
+                let at = self.exram[0x3C0 + (y / 32) as usize * 8 + (column / 4) as usize];
+                let shift = ((y / 16) & 1) * 4 + ((column / 2) & 1) * 2;
+                (at >> shift & 3) * 0x55
+            } else {
+                self.exram[(y / 8) as usize * 32 + column as usize]
+            };
+            return Some(v);
+        }
+
+        let v = match self.nametable_source(addr) {
+            2 if self.exram_mode <= 1 => Some(self.exram[offset]),
+            2 => Some(0),
+            3 if attribute => Some(self.fill_color * 0x55),
+            3 => Some(self.fill_tile),
+            // CIRAM
+            _ => None,
+        };
+        if self.exram_mode == 1 && self.in_frame {
+            if attribute {
+                if let Tile::Extended(ex) = self.tile {
+                    return Some((ex >> 6) * 0x55);
+                }
+            } else {
+                self.tile = Tile::Extended(self.exram[offset]);
+            }
+        } else {
+            self.tile = Tile::Normal;
+        }
+        v
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8) -> bool {
+        match self.nametable_source(addr) {
+            2 => {
+                if self.exram_mode <= 1 {
+                    self.exram[(addr & 0x3FF) as usize] = value;
+                }
+                true
+            }
+            3 => true,
+            _ => false,
+        }
+    }
+
+    fn snoop_ppu_register(&mut self, addr: u16, value: u8) {
+        match addr & 0x2007 {
+            0x2000 => self.large_sprites = value & 0x20 != 0,
+            0x2001 => {
+                self.rendering = value & 0x18 != 0;
+                if !self.rendering {
+                    self.in_frame = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // $5105 as the closest mirroring, for nametables in CIRAM
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables {
+            0x44 => Mirroring::Vertical,
+            0x50 => Mirroring::Horizontal,
+            0x55 => Mirroring::SingleScreenUpper,
+            _ => Mirroring::SingleScreenLower,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        // the PPU stopped reading: rendering is off or in vblank
+        if self.idle_cycles < 3 {
+            self.idle_cycles += 1;
+            if self.idle_cycles == 3 {
+                self.in_frame = false;
+            }
+        }
+        self.audio.tick();
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || self.pcm_irq
+    }
+
+    // PCM at the full scale of a pulse
+    fn audio_output(&self) -> f32 {
+        (self.audio.output() as f32 + self.pcm as f32 / 17.0) / 45.0
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        policy.fill(&mut self.exram, stream);
+        self.prg_mode = 3;
+        self.prg_banks = [0, 0, 0, 0, 0xFF];
+        self.prg_ram_protect = [0, 0];
+        self.chr_mode = 0;
+        self.chr_a = [0; 8];
+        self.chr_b = [0; 4];
+        self.chr_upper = 0;
+        self.last_chr_b = false;
+        self.exram_mode = 0;
+        self.nametables = 0;
+        self.fill_tile = 0;
+        self.fill_color = 0;
+        self.split = 0;
+        self.split_scroll = 0;
+        self.split_bank = 0;
+        self.multiplicand = 0xFF;
+        self.multiplier = 0xFF;
+        self.pcm_read_mode = false;
+        self.pcm_irq_enabled = false;
+        self.pcm_irq = false;
+        self.pcm = 0;
+        self.audio = Mmc5Audio::new();
+        self.large_sprites = false;
+        self.rendering = false;
+        self.last_read = 0;
+        self.same_reads = 0;
+        self.fetches = 0;
+        self.idle_cycles = 0;
+        self.in_frame = false;
+        self.scanline = 0;
+        self.irq_compare = 0;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+        self.tile = Tile::Normal;
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.exram.hash(&mut state);
+        (self.prg_mode, self.prg_banks, self.prg_ram_protect).hash(&mut state);
+        (
+            self.chr_mode,
+            self.chr_a,
+            self.chr_b,
+            self.chr_upper,
+            self.last_chr_b,
+        )
+            .hash(&mut state);
+        (
+            self.exram_mode,
+            self.nametables,
+            self.fill_tile,
+            self.fill_color,
+        )
+            .hash(&mut state);
+        (self.split, self.split_scroll, self.split_bank).hash(&mut state);
+        (self.multiplicand, self.multiplier).hash(&mut state);
+        (
+            self.pcm_read_mode,
+            self.pcm_irq_enabled,
+            self.pcm_irq,
+            self.pcm,
+        )
+            .hash(&mut state);
+        self.audio.hash(&mut state);
+        (self.large_sprites, self.rendering).hash(&mut state);
+        (
+            self.last_read,
+            self.same_reads,
+            self.fetches,
+            self.idle_cycles,
+        )
+            .hash(&mut state);
+        (self.in_frame, self.scanline, self.irq_compare).hash(&mut state);
+        (self.irq_enabled, self.irq_pending, self.tile).hash(&mut state);
+    }
+}
+
+// PRG RAM from the NES 2.0 header, or 64KB, the most boards have, as .sav files of other emulators
+fn prg_ram_size(rom: &Rom) -> usize {
+    if rom.header.nes2 {
+        rom.header.prg_ram_size + rom.header.prg_nvram_size
+    } else {
+        0x10000
+    }
+}
@@ -0,0 +1,170 @@
+// Sound of the MMC5: two pulse channels
+// https://wiki.nesdev.org/w/index.php?title=MMC5_audio
+//
+// The pulses are those of the APU without the sweep unit, and periods under 8 aren't silenced.
+// Their envelopes and length counters are clocked at a fixed 240Hz instead of by a frame counter.
+// The PCM channel is only a register and stays in the mapper.
+
+// CPU cycles per envelope and length counter clock
+const FRAME_CYCLES: u16 = 7457;
+
+const LENGTHS: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTIES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+#[derive(Debug, Default, Clone, Hash)]
+struct Pulse {
+    duty: u8,
+    // also the envelope loop
+    halt: bool,
+    constant: bool,
+    // constant volume, or envelope period
+    volume: u8,
+    period: u16,
+    enabled: bool,
+    length: u8,
+
+    timer: u16,
+    step: u8,
+
+    envelope_start: bool,
+    envelope_divider: u8,
+    decay: u8,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.halt = value & 0x20 != 0;
+                self.constant = value & 0x10 != 0;
+                self.volume = value & 0x0F;
+            }
+            // $5001 would be the sweep
+            1 => {}
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x07) as u16) << 8;
+                if self.enabled {
+                    self.length = LENGTHS[(value >> 3) as usize];
+                }
+                self.step = 0;
+                self.envelope_start = true;
+            }
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length = 0;
+        }
+    }
+
+    // every APU cycle
+    fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) & 7;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_frame(&mut self) {
+        if self.envelope_start {
+            self.envelope_start = false;
+            self.decay = 15;
+            self.envelope_divider = self.volume;
+        } else if self.envelope_divider == 0 {
+            self.envelope_divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.halt {
+                self.decay = 15;
+            }
+        } else {
+            self.envelope_divider -= 1;
+        }
+
+        if !self.halt && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    // 0-15
+    fn output(&self) -> u8 {
+        if self.length == 0 || DUTIES[self.duty as usize][self.step as usize] == 0 {
+            0
+        } else if self.constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash)]
+pub(super) struct Mmc5Audio {
+    pulses: [Pulse; 2],
+    // CPU cycles since the last envelope and length counter clock
+    frame_cycles: u16,
+    odd_cycle: bool,
+}
+
+impl Mmc5Audio {
+    pub(super) fn new() -> Self {
+        Self {
+            pulses: Default::default(),
+            frame_cycles: 0,
+            odd_cycle: false,
+        }
+    }
+
+    // $5000-$5007 and $5015
+    pub(super) fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5003 => self.pulses[0].write(addr & 3, value),
+            0x5004..=0x5007 => self.pulses[1].write(addr & 3, value),
+            _ => {
+                self.pulses[0].set_enabled(value & 0x01 != 0);
+                self.pulses[1].set_enabled(value & 0x02 != 0);
+            }
+        }
+    }
+
+    // $5015: whether the length counters are running
+    pub(super) fn status(&self) -> u8 {
+        (self.pulses[0].length > 0) as u8 | ((self.pulses[1].length > 0) as u8) << 1
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            for pulse in &mut self.pulses {
+                pulse.tick();
+            }
+        }
+        self.frame_cycles += 1;
+        if self.frame_cycles == FRAME_CYCLES {
+            self.frame_cycles = 0;
+            for pulse in &mut self.pulses {
+                pulse.clock_frame();
+            }
+        }
+    }
+
+    /// Sum of both pulses, from 0 to 30
+    pub(super) fn output(&self) -> u8 {
+        self.pulses[0].output() + self.pulses[1].output()
+    }
+}
@@ -0,0 +1,323 @@
+use std::hash::{Hash, Hasher};
+
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_019
+// https://wiki.nesdev.org/w/index.php?title=Namco_163_audio
+//
+// Namco 163: three switchable 8KB PRG banks with the last fixed, eight 1KB CHR banks, four
+// nametable banks that can point into CHR ROM, a 15-bit IRQ counter counting CPU cycles up to
+// $7FFF, and 128 bytes of internal RAM holding the waveforms and registers of up to eight
+// wavetable channels. The internal RAM is battery-backed along with PRG RAM on some boards.
+//
+// CHR banks pointing into CIRAM aren't supported, the cartridge can't reach it.
+#[derive(Clone)]
+pub(super) struct Namco163 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    nametables: [u8; 4],
+    // $F800: write protection of PRG RAM, and the address of the internal RAM
+    protect: u8,
+    sound_disabled: bool,
+
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq: bool,
+
+    sound_ram: [u8; 0x80],
+    address: u8,
+    auto_increment: bool,
+    // CPU cycles until the next channel update, and the channel updated
+    cycles: u8,
+    channel: u8,
+    outputs: [u8; 8],
+}
+
+impl Namco163 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; 0x2000],
+            chr,
+            chr_ram,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            nametables: [0xE0, 0xE1, 0xE0, 0xE1],
+            protect: 0,
+            sound_disabled: false,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq: false,
+            sound_ram: [0; 0x80],
+            address: 0,
+            auto_increment: false,
+            cycles: 0,
+            channel: 7,
+            outputs: [0; 8],
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank = match (addr - 0x8000) / 0x2000 {
+            n @ 0..=2 => self.prg_banks[n as usize] as usize,
+            _ => self.prg_rom.len() / 0x2000 - 1,
+        };
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_rom.len()
+    }
+
+    fn chr_addr(&self, bank: u8, addr: u16) -> usize {
+        (bank as usize * 0x400 + (addr & 0x3FF) as usize) % self.chr.len()
+    }
+
+    // PRG RAM is writable in 2KB blocks once $F800 is $4x
+    fn prg_ram_writable(&self, addr: u16) -> bool {
+        self.protect & 0xF0 == 0x40 && self.protect & (1 << ((addr - 0x6000) >> 11)) == 0
+    }
+
+    // Nametable bank of $2000-$2FFF, if in CHR ROM rather than CIRAM
+    fn nametable_bank(&self, addr: u16) -> Option<u8> {
+        let bank = self.nametables[((addr >> 10) & 3) as usize];
+        (bank < 0xE0).then_some(bank)
+    }
+
+    fn enabled_channels(&self) -> u8 {
+        (self.sound_ram[0x7F] >> 4 & 7) + 1
+    }
+
+    // One channel is updated every 15 CPU cycles, from the last one down
+    fn update_channel(&mut self) {
+        let base = 0x40 + self.channel as usize * 8;
+        let registers = &mut self.sound_ram[base..base + 8];
+        let frequency =
+            registers[0] as u32 | (registers[2] as u32) << 8 | ((registers[4] & 3) as u32) << 16;
+        let length = (256 - (registers[4] & 0xFC) as u32) << 16;
+        let mut phase =
+            registers[1] as u32 | (registers[3] as u32) << 8 | (registers[5] as u32) << 16;
+        phase = (phase + frequency) % length;
+        registers[1] = phase as u8;
+        registers[3] = (phase >> 8) as u8;
+        registers[5] = (phase >> 16) as u8;
+
+        let sample = (((phase >> 16) + registers[6] as u32) & 0xFF) as usize;
+        let volume = registers[7] & 0x0F;
+        let byte = self.sound_ram[sample / 2];
+        let nibble = if sample & 1 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        };
+        self.outputs[self.channel as usize] = nibble * volume;
+
+        let first = 8 - self.enabled_channels();
+        self.channel = if self.channel <= first {
+            7
+        } else {
+            self.channel - 1
+        };
+    }
+
+    fn sound_peek(&self) -> u8 {
+        self.sound_ram[self.address as usize]
+    }
+
+    fn advance_address(&mut self) {
+        if self.auto_increment {
+            self.address = (self.address + 1) & 0x7F;
+        }
+    }
+}
+
+impl Mapper for Namco163 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x4800..=0x4FFF => self.sound_peek(),
+            0x5000..=0x57FF => self.irq_counter as u8,
+            0x5800..=0x5FFF => (self.irq_counter >> 8) as u8 | (self.irq_enabled as u8) << 7,
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let value = self.cpu_peek(addr);
+        if let 0x4800..=0x4FFF = addr {
+            self.advance_address();
+        }
+        value
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => {
+                self.sound_ram[self.address as usize] = value;
+                self.advance_address();
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((value & 0x7F) as u16) << 8;
+                self.irq_enabled = value & 0x80 != 0;
+                self.irq = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable(addr) => {
+                self.prg_ram[(addr - 0x6000) as usize] = value
+            }
+            0x8000..=0xBFFF => self.chr_banks[((addr - 0x8000) >> 11) as usize] = value,
+            0xC000..=0xDFFF => self.nametables[((addr - 0xC000) >> 11) as usize] = value,
+            0xE000..=0xE7FF => {
+                self.prg_banks[0] = value & 0x3F;
+                self.sound_disabled = value & 0x40 != 0;
+            }
+            0xE800..=0xEFFF => self.prg_banks[1] = value & 0x3F,
+            0xF000..=0xF7FF => self.prg_banks[2] = value & 0x3F,
+            0xF800..=0xFFFF => {
+                self.protect = value;
+                self.address = value & 0x7F;
+                self.auto_increment = value & 0x80 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank = self.chr_banks[(addr >> 10) as usize & 7];
+        self.chr[self.chr_addr(bank, addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let bank = self.chr_banks[(addr >> 10) as usize & 7];
+            let addr = self.chr_addr(bank, addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn nametable_read(&mut self, addr: u16) -> Option<u8> {
+        let bank = self.nametable_bank(addr)?;
+        Some(self.chr[self.chr_addr(bank, addr)])
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8) -> bool {
+        match self.nametable_bank(addr) {
+            Some(bank) => {
+                if self.chr_ram {
+                    let addr = self.chr_addr(bank, addr);
+                    self.chr[addr] = value;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The CIRAM pages of the nametable banks as the closest mirroring
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables.map(|bank| bank & 1) {
+            [0, 1, 0, 1] => Mirroring::Vertical,
+            [0, 0, 1, 1] => Mirroring::Horizontal,
+            [1, 1, 1, 1] => Mirroring::SingleScreenUpper,
+            _ => Mirroring::SingleScreenLower,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq = true;
+            }
+        }
+
+        self.cycles += 1;
+        if self.cycles == 15 {
+            self.cycles = 0;
+            if !self.sound_disabled {
+                self.update_channel();
+            }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+
+    // The chip outputs one channel at a time, heard as their average
+    fn audio_output(&self) -> f32 {
+        if self.sound_disabled {
+            return 0.0;
+        }
+        let count = self.enabled_channels() as usize;
+        let sum: u32 = self.outputs[8 - count..].iter().map(|&o| o as u32).sum();
+        sum as f32 / (count as f32 * 225.0)
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        policy.fill(&mut self.sound_ram, stream);
+        self.prg_banks = [0; 3];
+        self.chr_banks = [0; 8];
+        self.nametables = [0xE0, 0xE1, 0xE0, 0xE1];
+        self.protect = 0;
+        self.sound_disabled = false;
+        self.irq_counter = 0;
+        self.irq_enabled = false;
+        self.irq = false;
+        self.address = 0;
+        self.auto_increment = false;
+        self.cycles = 0;
+        self.channel = 7;
+        self.outputs = [0; 8];
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    // PRG RAM then the internal RAM
+    fn save_data(&self) -> Vec<u8> {
+        [&self.prg_ram[..], &self.sound_ram[..]].concat()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let (prg_ram, sound_ram) = data.split_at(data.len().min(self.prg_ram.len()));
+        self.prg_ram[..prg_ram.len()].copy_from_slice(prg_ram);
+        let len = sound_ram.len().min(self.sound_ram.len());
+        self.sound_ram[..len].copy_from_slice(&sound_ram[..len]);
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.prg_banks.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.nametables.hash(&mut state);
+        self.protect.hash(&mut state);
+        self.sound_disabled.hash(&mut state);
+        self.irq_counter.hash(&mut state);
+        self.irq_enabled.hash(&mut state);
+        self.irq.hash(&mut state);
+        self.sound_ram.hash(&mut state);
+        self.address.hash(&mut state);
+        self.auto_increment.hash(&mut state);
+        self.cycles.hash(&mut state);
+        self.channel.hash(&mut state);
+        self.outputs.hash(&mut state);
+    }
+}
@@ -0,0 +1,82 @@
+use std::hash::{Hash, Hasher};
+
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=NROM
+#[derive(Clone)]
+pub(super) struct Nrom {
+    prg_rom: Vec<u8>,
+    // Family Basic has PRG RAM at $6000, and test ROMs report their status there
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub(super) fn new(rom: Rom) -> Self {
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; 0x2000],
+            chr,
+            chr_ram,
+            mirroring: rom.header.mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            // NROM-128 mirrors its 16KB
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => {
+                self.prg_rom[(addr - 0x8000) as usize % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram[(addr - 0x6000) as usize] = value;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            self.chr[addr as usize % len] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+    }
+}
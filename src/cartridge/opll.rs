@@ -0,0 +1,443 @@
+// FM synthesizer of the VRC7, a cut-down YM2413 (OPLL)
+// https://wiki.nesdev.org/w/index.php?title=VRC7_audio
+// https://github.com/nukeykt/Nuked-OPLL
+//
+// Six two-operator channels, each playing the custom instrument or one of 15 built into the chip,
+// without the YM2413's rhythm mode. Each operator looks up a quarter sine in a log-sin table, adds
+// its attenuation in the log domain and converts back through an exp table, so envelopes, total
+// level, key scaling and tremolo are all sums. The chip makes a sample every 72 cycles of its
+// 3.58MHz clock, every 36 CPU cycles.
+
+// Instruments 1-15, as dumped from the chip
+const INSTRUMENTS: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+const CPU_CYCLES_PER_SAMPLE: u8 = 36;
+
+// Frequency multipliers, doubled
+const MULTIPLIERS: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+// Key scale level attenuation for the high 4 bits of F-number, in 0.75dB
+const KEY_SCALE_LEVELS: [i32; 16] = [
+    0, 32, 40, 45, 48, 51, 53, 55, 56, 58, 59, 60, 61, 62, 63, 64,
+];
+
+// Vibrato: F-number offsets for the high 3 bits of F-number, over the 8 steps of the cycle
+const VIBRATO: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, -1, 0],
+    [0, 1, 2, 1, 0, -1, -2, -1],
+    [0, 1, 3, 1, 0, -1, -3, -1],
+    [0, 2, 4, 2, 0, -2, -4, -2],
+    [0, 2, 5, 2, 0, -2, -5, -2],
+    [0, 3, 6, 3, 0, -3, -6, -3],
+    [0, 3, 7, 3, 0, -3, -7, -3],
+];
+
+// Envelope steps taken every 8 updates, for the low 2 bits of a rate
+const ENVELOPE_STEPS: [[u16; 8]; 4] = [
+    [0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 1, 0, 1, 1, 1, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 1],
+];
+
+// Attenuations are in units of 0.1875dB, 9 bits like the envelope
+const MAX_ATTENUATION: u16 = 0x1FF;
+
+// Log-sin ROM of the chip, shared with the YM3812 it was read from: -log2(sin) of a quarter wave,
+// in 1/256 octaves
+const LOG_SIN: [u16; 256] = [
+    0x859, 0x6C3, 0x607, 0x58B, 0x52E, 0x4E4, 0x4A6, 0x471, 0x443, 0x41A, 0x3F5, 0x3D3, 0x3B5,
+    0x398, 0x37E, 0x365, 0x34E, 0x339, 0x324, 0x311, 0x2FF, 0x2ED, 0x2DC, 0x2CD, 0x2BD, 0x2AF,
+    0x2A0, 0x293, 0x286, 0x279, 0x26D, 0x261, 0x256, 0x24B, 0x240, 0x236, 0x22C, 0x222, 0x218,
+    0x20F, 0x206, 0x1FD, 0x1F5, 0x1EC, 0x1E4, 0x1DC, 0x1D4, 0x1CD, 0x1C5, 0x1BE, 0x1B7, 0x1B0,
+    0x1A9, 0x1A2, 0x19B, 0x195, 0x18F, 0x188, 0x182, 0x17C, 0x177, 0x171, 0x16B, 0x166, 0x160,
+    0x15B, 0x155, 0x150, 0x14B, 0x146, 0x141, 0x13C, 0x137, 0x133, 0x12E, 0x129, 0x125, 0x121,
+    0x11C, 0x118, 0x114, 0x10F, 0x10B, 0x107, 0x103, 0x0FF, 0x0FB, 0x0F8, 0x0F4, 0x0F0, 0x0EC,
+    0x0E9, 0x0E5, 0x0E2, 0x0DE, 0x0DB, 0x0D7, 0x0D4, 0x0D1, 0x0CD, 0x0CA, 0x0C7, 0x0C4, 0x0C1,
+    0x0BE, 0x0BB, 0x0B8, 0x0B5, 0x0B2, 0x0AF, 0x0AC, 0x0A9, 0x0A7, 0x0A4, 0x0A1, 0x09F, 0x09C,
+    0x099, 0x097, 0x094, 0x092, 0x08F, 0x08D, 0x08A, 0x088, 0x086, 0x083, 0x081, 0x07F, 0x07D,
+    0x07A, 0x078, 0x076, 0x074, 0x072, 0x070, 0x06E, 0x06C, 0x06A, 0x068, 0x066, 0x064, 0x062,
+    0x060, 0x05E, 0x05C, 0x05B, 0x059, 0x057, 0x055, 0x053, 0x052, 0x050, 0x04E, 0x04D, 0x04B,
+    0x04A, 0x048, 0x046, 0x045, 0x043, 0x042, 0x040, 0x03F, 0x03E, 0x03C, 0x03B, 0x039, 0x038,
+    0x037, 0x035, 0x034, 0x033, 0x031, 0x030, 0x02F, 0x02E, 0x02D, 0x02B, 0x02A, 0x029, 0x028,
+    0x027, 0x026, 0x025, 0x024, 0x023, 0x022, 0x021, 0x020, 0x01F, 0x01E, 0x01D, 0x01C, 0x01B,
+    0x01A, 0x019, 0x018, 0x017, 0x017, 0x016, 0x015, 0x014, 0x014, 0x013, 0x012, 0x011, 0x011,
+    0x010, 0x00F, 0x00F, 0x00E, 0x00D, 0x00D, 0x00C, 0x00C, 0x00B, 0x00A, 0x00A, 0x009, 0x009,
+    0x008, 0x008, 0x007, 0x007, 0x007, 0x006, 0x006, 0x005, 0x005, 0x005, 0x004, 0x004, 0x004,
+    0x003, 0x003, 0x003, 0x002, 0x002, 0x002, 0x002, 0x001, 0x001, 0x001, 0x001, 0x001, 0x001,
+    0x001, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000,
+];
+
+// Exp ROM of the chip: 2^x - 1 for the fraction of a level, in 1/1024
+const EXP: [u16; 256] = [
+    0x000, 0x003, 0x006, 0x008, 0x00B, 0x00E, 0x011, 0x014, 0x016, 0x019, 0x01C, 0x01F, 0x022,
+    0x025, 0x028, 0x02A, 0x02D, 0x030, 0x033, 0x036, 0x039, 0x03C, 0x03F, 0x042, 0x045, 0x048,
+    0x04B, 0x04E, 0x051, 0x054, 0x057, 0x05A, 0x05D, 0x060, 0x063, 0x066, 0x069, 0x06C, 0x06F,
+    0x072, 0x075, 0x078, 0x07B, 0x07E, 0x082, 0x085, 0x088, 0x08B, 0x08E, 0x091, 0x094, 0x098,
+    0x09B, 0x09E, 0x0A1, 0x0A4, 0x0A8, 0x0AB, 0x0AE, 0x0B1, 0x0B5, 0x0B8, 0x0BB, 0x0BE, 0x0C2,
+    0x0C5, 0x0C8, 0x0CC, 0x0CF, 0x0D2, 0x0D6, 0x0D9, 0x0DC, 0x0E0, 0x0E3, 0x0E7, 0x0EA, 0x0ED,
+    0x0F1, 0x0F4, 0x0F8, 0x0FB, 0x0FF, 0x102, 0x106, 0x109, 0x10C, 0x110, 0x114, 0x117, 0x11B,
+    0x11E, 0x122, 0x125, 0x129, 0x12C, 0x130, 0x134, 0x137, 0x13B, 0x13E, 0x142, 0x146, 0x149,
+    0x14D, 0x151, 0x154, 0x158, 0x15C, 0x160, 0x163, 0x167, 0x16B, 0x16F, 0x172, 0x176, 0x17A,
+    0x17E, 0x181, 0x185, 0x189, 0x18D, 0x191, 0x195, 0x199, 0x19C, 0x1A0, 0x1A4, 0x1A8, 0x1AC,
+    0x1B0, 0x1B4, 0x1B8, 0x1BC, 0x1C0, 0x1C4, 0x1C8, 0x1CC, 0x1D0, 0x1D4, 0x1D8, 0x1DC, 0x1E0,
+    0x1E4, 0x1E8, 0x1EC, 0x1F0, 0x1F5, 0x1F9, 0x1FD, 0x201, 0x205, 0x209, 0x20E, 0x212, 0x216,
+    0x21A, 0x21E, 0x223, 0x227, 0x22B, 0x230, 0x234, 0x238, 0x23C, 0x241, 0x245, 0x249, 0x24E,
+    0x252, 0x257, 0x25B, 0x25F, 0x264, 0x268, 0x26D, 0x271, 0x276, 0x27A, 0x27F, 0x283, 0x288,
+    0x28C, 0x291, 0x295, 0x29A, 0x29E, 0x2A3, 0x2A8, 0x2AC, 0x2B1, 0x2B5, 0x2BA, 0x2BF, 0x2C4,
+    0x2C8, 0x2CD, 0x2D2, 0x2D6, 0x2DB, 0x2E0, 0x2E5, 0x2E9, 0x2EE, 0x2F3, 0x2F8, 0x2FD, 0x302,
+    0x306, 0x30B, 0x310, 0x315, 0x31A, 0x31F, 0x324, 0x329, 0x32E, 0x333, 0x338, 0x33D, 0x342,
+    0x347, 0x34C, 0x351, 0x356, 0x35B, 0x360, 0x365, 0x36A, 0x370, 0x375, 0x37A, 0x37F, 0x384,
+    0x38A, 0x38F, 0x394, 0x399, 0x39F, 0x3A4, 0x3A9, 0x3AE, 0x3B4, 0x3B9, 0x3BF, 0x3C4, 0x3C9,
+    0x3CF, 0x3D4, 0x3DA, 0x3DF, 0x3E4, 0x3EA, 0x3EF, 0x3F5, 0x3FA,
+];
+
+// One sample of an operator, about ±4095
+fn operator(phase: i32, attenuation: u16, rectified: bool) -> i32 {
+    let phase = phase & 0x3FF;
+    let negative = phase & 0x200 != 0;
+    if negative && rectified {
+        return 0;
+    }
+    let quarter = if phase & 0x100 != 0 {
+        !phase & 0xFF
+    } else {
+        phase & 0xFF
+    };
+    let level = LOG_SIN[quarter as usize] as u32 + ((attenuation as u32) << 3);
+    let shift = level >> 8;
+    if shift >= 13 {
+        return 0;
+    }
+    // the exp ROM is read backwards, with the leading 1 implied
+    let magnitude = (((EXP[(!level & 0xFF) as usize] | 0x400) << 1) >> shift) as i32;
+    if negative {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+// Parameters of the modulator (0) or carrier (1) of an instrument
+struct Patch {
+    tremolo: bool,
+    vibrato: bool,
+    // sustained tone, held at the sustain level until key off
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    rectified: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+impl Patch {
+    fn new(instrument: &[u8; 8], slot: usize) -> Self {
+        let flags = instrument[slot];
+        Self {
+            tremolo: flags & 0x80 != 0,
+            vibrato: flags & 0x40 != 0,
+            sustained: flags & 0x20 != 0,
+            key_scale_rate: flags & 0x10 != 0,
+            multiplier: flags & 0x0F,
+            key_scale_level: instrument[2 + slot] >> 6,
+            rectified: instrument[3] & (0x08 << slot) != 0,
+            attack: instrument[4 + slot] >> 4,
+            decay: instrument[4 + slot] & 0x0F,
+            sustain_level: instrument[6 + slot] >> 4,
+            release: instrument[6 + slot] & 0x0F,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+enum Envelope {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+#[derive(Debug, Clone, Hash)]
+struct Slot {
+    // 19 bits, the high 10 index the sine
+    phase: u32,
+    envelope: Envelope,
+    attenuation: u16,
+    // last two outputs, for the feedback of the modulator
+    outputs: [i32; 2],
+}
+
+impl Default for Slot {
+    fn default() -> Self {
+        Self {
+            phase: 0,
+            envelope: Envelope::Off,
+            attenuation: MAX_ATTENUATION,
+            outputs: [0; 2],
+        }
+    }
+}
+
+impl Slot {
+    fn key_on(&mut self) {
+        self.envelope = Envelope::Attack;
+        self.phase = 0;
+    }
+
+    fn key_off(&mut self) {
+        if self.envelope != Envelope::Off {
+            self.envelope = Envelope::Release;
+        }
+    }
+
+    fn update_envelope(&mut self, patch: &Patch, key_scale: u8, sustain: bool, counter: u32) {
+        let rate = match self.envelope {
+            Envelope::Attack => patch.attack,
+            Envelope::Decay => patch.decay,
+            Envelope::Sustain if patch.sustained => 0,
+            // percussive tones keep decaying
+            Envelope::Sustain => patch.release,
+            Envelope::Release if sustain => 5,
+            Envelope::Release if patch.sustained => patch.release,
+            Envelope::Release => 7,
+            Envelope::Off => 0,
+        };
+        let rate = if rate == 0 {
+            0
+        } else {
+            let offset = if patch.key_scale_rate {
+                key_scale
+            } else {
+                key_scale >> 2
+            };
+            (rate * 4 + offset).min(63)
+        };
+
+        if self.envelope == Envelope::Attack && rate >= 60 {
+            self.attenuation = 0;
+        }
+        let steps = envelope_steps(rate, counter);
+        match self.envelope {
+            Envelope::Attack => {
+                // exponential, faster the louder it gets
+                let delta = ((self.attenuation as u32 + 1) * steps as u32).div_ceil(8);
+                self.attenuation = self.attenuation.saturating_sub(delta as u16);
+                if self.attenuation == 0 {
+                    self.envelope = Envelope::Decay;
+                }
+            }
+            Envelope::Off => {}
+            _ => {
+                self.attenuation = (self.attenuation + steps).min(MAX_ATTENUATION);
+                if self.envelope == Envelope::Decay
+                    && self.attenuation >= (patch.sustain_level as u16) << 4
+                {
+                    self.envelope = Envelope::Sustain;
+                }
+                if self.envelope == Envelope::Release && self.attenuation == MAX_ATTENUATION {
+                    self.envelope = Envelope::Off;
+                }
+            }
+        }
+    }
+}
+
+// Steps the envelope takes this sample: rates below 52 step once every few samples, faster
+// rates several times per sample
+fn envelope_steps(rate: u8, counter: u32) -> u16 {
+    if rate < 4 {
+        return 0;
+    }
+    let high = rate as u32 >> 2;
+    let low = (rate & 3) as usize;
+    let shift = 13u32.saturating_sub(high);
+    if counter & ((1 << shift) - 1) != 0 {
+        return 0;
+    }
+    ENVELOPE_STEPS[low][((counter >> shift) & 7) as usize] << high.saturating_sub(13)
+}
+
+#[derive(Debug, Default, Clone, Hash)]
+struct Channel {
+    slots: [Slot; 2],
+}
+
+#[derive(Debug, Clone, Hash)]
+pub(super) struct Opll {
+    address: u8,
+    registers: [u8; 0x40],
+    channels: [Channel; 6],
+    cycles: u8,
+    // sample counter for the envelope, tremolo and vibrato
+    counter: u32,
+    output: i32,
+}
+
+impl Opll {
+    pub(super) fn new() -> Self {
+        Self {
+            address: 0,
+            registers: [0; 0x40],
+            channels: Default::default(),
+            cycles: 0,
+            counter: 0,
+            output: 0,
+        }
+    }
+
+    pub(super) fn select(&mut self, address: u8) {
+        self.address = address;
+    }
+
+    pub(super) fn write(&mut self, value: u8) {
+        let address = self.address as usize;
+        if address >= self.registers.len() {
+            return;
+        }
+        // key on and off take effect on the edge
+        if let 0x20..=0x25 = address {
+            let was_on = self.registers[address] & 0x10 != 0;
+            let on = value & 0x10 != 0;
+            let channel = &mut self.channels[address - 0x20];
+            for slot in &mut channel.slots {
+                match (was_on, on) {
+                    (false, true) => slot.key_on(),
+                    (true, false) => slot.key_off(),
+                    _ => {}
+                }
+            }
+        }
+        self.registers[address] = value;
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles == CPU_CYCLES_PER_SAMPLE {
+            self.cycles = 0;
+            self.sample();
+        }
+    }
+
+    /// Last sample, centred on 0.5
+    pub(super) fn output(&self) -> f32 {
+        0.5 + self.output as f32 / (6.0 * 4096.0 * 2.0)
+    }
+
+    fn instrument(&self, channel: usize) -> [u8; 8] {
+        match self.registers[0x30 + channel] >> 4 {
+            0 => self.registers[..8].try_into().unwrap(),
+            n => INSTRUMENTS[n as usize - 1],
+        }
+    }
+
+    // Tremolo of 4.8dB at 3.7Hz, a triangle over 13440 samples
+    fn tremolo(&self) -> u16 {
+        let step = (self.counter >> 6) % 210;
+        let triangle = if step < 105 { step } else { 209 - step };
+        (triangle / 4) as u16
+    }
+
+    fn sample(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+        let tremolo = self.tremolo();
+        let vibrato_step = ((self.counter >> 10) & 7) as usize;
+
+        let mut output = 0;
+        for c in 0..self.channels.len() {
+            let instrument = self.instrument(c);
+            let patches = [Patch::new(&instrument, 0), Patch::new(&instrument, 1)];
+            let fnumber =
+                self.registers[0x10 + c] as u32 | (self.registers[0x20 + c] as u32 & 1) << 8;
+            let block = (self.registers[0x20 + c] >> 1) & 7;
+            let sustain = self.registers[0x20 + c] & 0x20 != 0;
+            let volume = self.registers[0x30 + c] & 0x0F;
+            let total_level = instrument[2] & 0x3F;
+            let feedback = instrument[3] & 0x07;
+
+            // octave and the high bit of F-number
+            let key_scale = block << 1 | (fnumber >> 8) as u8;
+            let key_scale_level = ((KEY_SCALE_LEVELS[(fnumber >> 5) as usize] << 2)
+                - ((8 - block as i32) << 5))
+                .max(0);
+
+            let counter = self.counter;
+            let channel = &mut self.channels[c];
+            let mut attenuations = [0; 2];
+            for (s, (slot, patch)) in channel.slots.iter_mut().zip(&patches).enumerate() {
+                slot.update_envelope(patch, key_scale, sustain, counter);
+
+                let level = match s {
+                    0 => (total_level as u16) << 2,
+                    _ => (volume as u16) << 4,
+                };
+                // none, 1.5, 3 and 6dB per octave
+                let key_scale_level = match patch.key_scale_level {
+                    0 => 0,
+                    1 => key_scale_level >> 2,
+                    2 => key_scale_level >> 1,
+                    _ => key_scale_level,
+                } as u16;
+                let tremolo = if patch.tremolo { tremolo } else { 0 };
+                attenuations[s] =
+                    (slot.attenuation + level + key_scale_level + tremolo).min(MAX_ATTENUATION);
+            }
+
+            let [modulator, carrier] = &mut channel.slots;
+            let feedback = match feedback {
+                0 => 0,
+                n => (modulator.outputs[0] + modulator.outputs[1]) >> (9 - n),
+            };
+            let modulation = operator(
+                (modulator.phase >> 9) as i32 + feedback,
+                attenuations[0],
+                patches[0].rectified,
+            );
+            modulator.outputs = [modulator.outputs[1], modulation];
+            let sample = operator(
+                (carrier.phase >> 9) as i32 + modulation,
+                attenuations[1],
+                patches[1].rectified,
+            );
+            if carrier.envelope != Envelope::Off {
+                output += sample;
+            }
+
+            for (slot, patch) in channel.slots.iter_mut().zip(&patches) {
+                let vibrato = if patch.vibrato {
+                    VIBRATO[(fnumber >> 6) as usize][vibrato_step]
+                } else {
+                    0
+                };
+                let increment = (((fnumber as i32 * 2 + vibrato) as u32
+                    * MULTIPLIERS[patch.multiplier as usize])
+                    << block)
+                    >> 2;
+                slot.phase = (slot.phase + increment) & 0x7FFFF;
+            }
+        }
+        self.output = output;
+    }
+}
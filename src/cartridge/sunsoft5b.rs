@@ -0,0 +1,190 @@
+// Sound of the Sunsoft 5B, an AY-3-8910 inside the FME-7
+// https://wiki.nesdev.org/w/index.php?title=Sunsoft_5B_audio
+//
+// Three square channels, each mixed with a shared noise generator and given either a fixed volume
+// or the shared envelope. Tones and noise are clocked every 16 CPU cycles. The envelope has 32
+// steps, twice the AY's, and every step of volume is 1.5dB, fixed volumes taking every other one.
+
+// Amplitude of the 32 levels, 1.5dB apart, with 0 silent
+const VOLUMES: [f32; 32] = [
+    0.0, 0.005623, 0.006683, 0.007943, 0.009441, 0.01122, 0.013335, 0.015849, 0.018836, 0.022387,
+    0.026607, 0.031623, 0.037584, 0.044668, 0.053088, 0.063096, 0.074989, 0.089125, 0.105925,
+    0.125893, 0.149624, 0.177828, 0.211349, 0.251189, 0.298538, 0.354813, 0.421697, 0.501187,
+    0.595662, 0.707946, 0.841395, 1.0,
+];
+
+// CPU cycles per tone and noise clock, and per envelope clock
+const TONE_DIVIDER: u8 = 16;
+const ENVELOPE_DIVIDER: u8 = 8;
+
+#[derive(Debug, Default, Clone, Hash)]
+struct Tone {
+    counter: u16,
+    output: bool,
+}
+
+#[derive(Debug, Clone, Hash)]
+pub(super) struct Sunsoft5b {
+    address: u8,
+    registers: [u8; 16],
+
+    tone_cycles: u8,
+    tones: [Tone; 3],
+
+    noise_counter: u8,
+    // 17-bit LFSR
+    noise: u32,
+
+    envelope_cycles: u8,
+    envelope_counter: u16,
+    // 0-31, counting up when attacking
+    envelope_step: u8,
+    attack: bool,
+    holding: bool,
+}
+
+impl Sunsoft5b {
+    pub(super) fn new() -> Self {
+        Self {
+            address: 0,
+            registers: [0; 16],
+            tone_cycles: 0,
+            tones: Default::default(),
+            noise_counter: 0,
+            noise: 1,
+            envelope_cycles: 0,
+            envelope_counter: 0,
+            envelope_step: 0,
+            attack: false,
+            holding: false,
+        }
+    }
+
+    pub(super) fn select(&mut self, address: u8) {
+        self.address = address;
+    }
+
+    pub(super) fn write(&mut self, value: u8) {
+        // the upper 4 bits of the address must be clear
+        if self.address > 0x0F {
+            return;
+        }
+        self.registers[self.address as usize] = value;
+        // writing the shape restarts the envelope
+        if self.address == 0x0D {
+            self.attack = value & 0x04 != 0;
+            self.envelope_step = 0;
+            self.envelope_counter = 0;
+            self.holding = false;
+        }
+    }
+
+    pub(super) fn tick(&mut self) {
+        self.tone_cycles += 1;
+        if self.tone_cycles == TONE_DIVIDER {
+            self.tone_cycles = 0;
+            self.clock_tones();
+        }
+        self.envelope_cycles += 1;
+        if self.envelope_cycles == ENVELOPE_DIVIDER {
+            self.envelope_cycles = 0;
+            self.clock_envelope();
+        }
+    }
+
+    /// Sum of the three channels, from 0 to 1
+    pub(super) fn output(&self) -> f32 {
+        let mixer = self.registers[7];
+        let noise = self.noise & 1 != 0;
+        let mut sum = 0.0;
+        for (i, tone) in self.tones.iter().enumerate() {
+            let tone_on = tone.output || mixer & (1 << i) != 0;
+            let noise_on = noise || mixer & (8 << i) != 0;
+            if tone_on && noise_on {
+                sum += VOLUMES[self.level(i) as usize];
+            }
+        }
+        sum / 3.0
+    }
+
+    // Level of a channel on the 32 steps of the envelope
+    fn level(&self, channel: usize) -> u8 {
+        let volume = self.registers[8 + channel];
+        if volume & 0x10 != 0 {
+            self.envelope_level()
+        } else if volume & 0x0F == 0 {
+            0
+        } else {
+            (volume & 0x0F) * 2 + 1
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.attack {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    fn period(&self, low: usize, high_mask: u8) -> u16 {
+        let period =
+            self.registers[low] as u16 | ((self.registers[low + 1] & high_mask) as u16) << 8;
+        period.max(1)
+    }
+
+    fn clock_tones(&mut self) {
+        for i in 0..3 {
+            let period = self.period(i * 2, 0x0F);
+            let tone = &mut self.tones[i];
+            tone.counter += 1;
+            if tone.counter >= period {
+                tone.counter = 0;
+                tone.output = !tone.output;
+            }
+        }
+
+        let period = (self.registers[6] & 0x1F).max(1);
+        self.noise_counter += 1;
+        if self.noise_counter >= period {
+            self.noise_counter = 0;
+            let bit = (self.noise ^ (self.noise >> 3)) & 1;
+            self.noise = (self.noise >> 1) | bit << 16;
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        let period = (self.registers[0x0B] as u16 | (self.registers[0x0C] as u16) << 8).max(1);
+        self.envelope_counter += 1;
+        if self.envelope_counter < period {
+            return;
+        }
+        self.envelope_counter = 0;
+        if self.holding {
+            return;
+        }
+
+        self.envelope_step += 1;
+        if self.envelope_step < 32 {
+            return;
+        }
+        // continue, attack, alternate, hold
+        let shape = self.registers[0x0D];
+        self.envelope_step = 31;
+        if shape & 0x08 == 0 {
+            // one decay or attack, then silence
+            self.attack = false;
+            self.holding = true;
+        } else if shape & 0x01 != 0 {
+            if shape & 0x02 != 0 {
+                self.attack = !self.attack;
+            }
+            self.holding = true;
+        } else {
+            if shape & 0x02 != 0 {
+                self.attack = !self.attack;
+            }
+            self.envelope_step = 0;
+        }
+    }
+}
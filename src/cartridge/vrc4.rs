@@ -0,0 +1,207 @@
+use std::hash::{Hash, Hasher};
+
+use super::vrc_irq::VrcIrq;
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=VRC2_and_VRC4
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_021
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_022
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_023
+// https://wiki.nesdev.org/w/index.php?title=INES_Mapper_025
+//
+// Konami VRC2 and VRC4: two switchable 8KB PRG banks, eight 1KB CHR banks written a nibble at a
+// time, and on the VRC4 an IRQ counter and a PRG swap mode. Boards wire different CPU address
+// lines to the two register select inputs, which is most of what tells the mappers apart.
+#[derive(Clone)]
+pub(super) struct Vrc4 {
+    prg_rom: Vec<u8>,
+    // VRC4 only, the VRC2 has a one-bit latch at $6000-$6FFF instead
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    vrc2: bool,
+    // address lines wired to register select 0 and 1, several of them when the board is unknown
+    select: [u16; 2],
+    // VRC2a ignores the low bit of CHR banks
+    chr_shift: u8,
+
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    mirroring: u8,
+    latch: u8,
+    irq: VrcIrq,
+}
+
+impl Vrc4 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let header = &rom.header;
+        // without a submapper, the lines of both boards a mapper number stands for are ORed
+        let (vrc2, select) = match (header.mapper, header.submapper) {
+            // VRC4a, VRC4c
+            (21, 1) => (false, [0x02, 0x04]),
+            (21, 2) => (false, [0x40, 0x80]),
+            (21, _) => (false, [0x42, 0x84]),
+            // VRC2a
+            (22, _) => (true, [0x02, 0x01]),
+            // VRC4f, VRC4e, VRC2b
+            (23, 1) => (false, [0x01, 0x02]),
+            (23, 2) => (false, [0x04, 0x08]),
+            (23, 3) => (true, [0x01, 0x02]),
+            (23, _) => (false, [0x05, 0x0A]),
+            // VRC4b, VRC4d, VRC2c
+            (25, 1) => (false, [0x02, 0x01]),
+            (25, 2) => (false, [0x08, 0x04]),
+            (25, 3) => (true, [0x02, 0x01]),
+            (_, _) => (false, [0x0A, 0x05]),
+        };
+        let chr_shift = (header.mapper == 22) as u8;
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: if vrc2 { vec![] } else { vec![0; 0x2000] },
+            chr,
+            chr_ram,
+            vrc2,
+            select,
+            chr_shift,
+            prg_banks: [0, 0],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirroring: 0,
+            latch: 0,
+            irq: VrcIrq::default(),
+        }
+    }
+
+    // $x000-$x003 from the address lines wired to the register select inputs
+    fn register(&self, addr: u16) -> u16 {
+        let bit = |mask: u16| (addr & mask != 0) as u16;
+        addr & 0xF000 | bit(self.select[1]) << 1 | bit(self.select[0])
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let last = (self.prg_rom.len() / 0x2000).max(1) - 1;
+        let bank = match ((addr - 0x8000) / 0x2000, self.prg_swap) {
+            (0, false) | (2, true) => self.prg_banks[0] as usize,
+            (0, true) | (2, false) => last - 1,
+            (1, _) => self.prg_banks[1] as usize,
+            _ => last,
+        };
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_rom.len()
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let bank = (self.chr_banks[(addr >> 10) as usize & 7] >> self.chr_shift) as usize;
+        (bank * 0x400 + (addr & 0x3FF) as usize) % self.chr.len()
+    }
+
+    fn write_chr_bank(&mut self, register: u16, value: u8) {
+        // $B000/$B001 for bank 0, $B002/$B003 for bank 1, and so on up to $E003
+        let i = ((register - 0xB000) >> 12) as usize * 2 + (register & 2) as usize / 2;
+        let bank = &mut self.chr_banks[i];
+        if register & 1 == 0 {
+            *bank = (*bank & !0x0F) | (value & 0x0F) as u16;
+        } else {
+            let high = if self.vrc2 { 0x0F } else { 0x1F };
+            *bank = (*bank & 0x0F) | ((value & high) as u16) << 4;
+        }
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            // the latch on D0, open bus above
+            0x6000..=0x6FFF if self.vrc2 => (addr >> 8) as u8 & 0xFE | self.latch,
+            0x6000..=0x7FFF if self.vrc2 => (addr >> 8) as u8,
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x6FFF if self.vrc2 => self.latch = value & 1,
+            0x6000..=0x7FFF if !self.vrc2 => self.prg_ram[(addr - 0x6000) as usize] = value,
+            0x8000..=0xFFFF => match self.register(addr) {
+                0x8000..=0x8003 => self.prg_banks[0] = value & 0x1F,
+                0x9000..=0x9003 if self.vrc2 => self.mirroring = value & 1,
+                0x9000 | 0x9001 => self.mirroring = value & 3,
+                0x9002 => self.prg_swap = value & 0x02 != 0,
+                0xA000..=0xA003 => self.prg_banks[1] = value & 0x1F,
+                r @ 0xB000..=0xEFFF => self.write_chr_bank(r, value),
+                _ if self.vrc2 => {}
+                0xF000 => self.irq.set_latch(self.irq.latch() & 0xF0 | value & 0x0F),
+                0xF001 => self.irq.set_latch(self.irq.latch() & 0x0F | value << 4),
+                0xF002 => self.irq.write_control(value),
+                0xF003 => self.irq.acknowledge(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        self.irq.tick();
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        self.prg_banks = [0, 0];
+        self.prg_swap = false;
+        self.chr_banks = [0; 8];
+        self.mirroring = 0;
+        self.latch = 0;
+        self.irq = VrcIrq::default();
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.prg_banks.hash(&mut state);
+        self.prg_swap.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.mirroring.hash(&mut state);
+        self.latch.hash(&mut state);
+        self.irq.hash(&mut state);
+    }
+}
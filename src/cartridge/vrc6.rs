@@ -0,0 +1,318 @@
+use std::hash::{Hash, Hasher};
+
+use super::vrc_irq::VrcIrq;
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=VRC6
+// https://wiki.nesdev.org/w/index.php?title=VRC6_audio
+//
+// Konami VRC6: a 16KB and an 8KB PRG bank with the last 8KB fixed, eight CHR registers, the VRC
+// IRQ counter, and two pulse channels and a sawtooth channel. Mapper 26 swaps the two register
+// select lines.
+//
+// Only the mirroring modes games use are supported: CIRAM nametables picked by bits 2-3 of $B003.
+#[derive(Clone)]
+pub(super) struct Vrc6 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    // mapper 26
+    swapped: bool,
+
+    prg_16k: u8,
+    prg_8k: u8,
+    chr_banks: [u8; 8],
+    // $B003
+    ppu_mode: u8,
+    irq: VrcIrq,
+
+    pulses: [Pulse; 2],
+    saw: Saw,
+    // $9003
+    halt: bool,
+    frequency_shift: u8,
+}
+
+#[derive(Debug, Default, Clone, Hash)]
+struct Pulse {
+    volume: u8,
+    duty: u8,
+    // constant volume, ignoring the duty
+    mode: bool,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+
+impl Pulse {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.mode = value & 0x80 != 0;
+                self.duty = (value >> 4) & 7;
+                self.volume = value & 0x0F;
+            }
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = (self.step + 1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // 0-15
+    fn output(&self) -> u8 {
+        if self.enabled && (self.mode || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Hash)]
+struct Saw {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Saw {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.rate = value & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    // the rate is added every other clock, six times, then the accumulator is cleared
+    fn tick(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period >> shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 1 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    // 0-31, the high 5 bits of the accumulator
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+impl Vrc6 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            swapped: rom.header.mapper == 26,
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; 0x2000],
+            chr,
+            chr_ram,
+            prg_16k: 0,
+            prg_8k: 0,
+            chr_banks: [0; 8],
+            ppu_mode: 0,
+            irq: VrcIrq::default(),
+            pulses: [Pulse::default(), Pulse::default()],
+            saw: Saw::default(),
+            halt: false,
+            frequency_shift: 0,
+        }
+    }
+
+    fn register(&self, addr: u16) -> u16 {
+        let select = if self.swapped {
+            (addr & 1) << 1 | (addr >> 1) & 1
+        } else {
+            addr & 3
+        };
+        addr & 0xF000 | select
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.ppu_mode & 0x80 != 0
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let offset = match addr {
+            0x8000..=0xBFFF => self.prg_16k as usize * 0x4000 + (addr & 0x3FFF) as usize,
+            0xC000..=0xDFFF => self.prg_8k as usize * 0x2000 + (addr & 0x1FFF) as usize,
+            _ => self.prg_rom.len() - 0x2000 + (addr & 0x1FFF) as usize,
+        };
+        offset % self.prg_rom.len()
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let slot = (addr >> 10) as usize & 7;
+        // 1KB banks, or 2KB banks taking PPU A10 as their low bit
+        let bank = match (self.ppu_mode & 3, slot) {
+            (0, n) => self.chr_banks[n],
+            (1, n) => self.chr_banks[n / 2] & !1 | (n & 1) as u8,
+            (_, n @ 0..=3) => self.chr_banks[n],
+            (_, n) => self.chr_banks[4 + (n - 4) / 2] & !1 | (n & 1) as u8,
+        };
+        (bank as usize * 0x400 + (addr & 0x3FF) as usize) % self.chr.len()
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram[(addr - 0x6000) as usize] = value
+            }
+            0x8000..=0xFFFF => match self.register(addr) {
+                0x8000..=0x8003 => self.prg_16k = value & 0x0F,
+                0x9003 => {
+                    self.halt = value & 0x01 != 0;
+                    self.frequency_shift = match value & 0x06 {
+                        0 => 0,
+                        2 => 4,
+                        _ => 8,
+                    };
+                }
+                r @ 0x9000..=0x9002 => self.pulses[0].write(r & 3, value),
+                r @ 0xA000..=0xA002 => self.pulses[1].write(r & 3, value),
+                r @ 0xB000..=0xB002 => self.saw.write(r & 3, value),
+                0xB003 => self.ppu_mode = value,
+                0xC000..=0xC003 => self.prg_8k = value & 0x1F,
+                r @ 0xD000..=0xE003 => {
+                    let i = ((r - 0xD000) >> 12) as usize * 4 + (r & 3) as usize;
+                    self.chr_banks[i] = value;
+                }
+                0xF000 => self.irq.set_latch(value),
+                0xF001 => self.irq.write_control(value),
+                0xF002 => self.irq.acknowledge(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.ppu_mode >> 2) & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        self.irq.tick();
+        if !self.halt {
+            for pulse in &mut self.pulses {
+                pulse.tick(self.frequency_shift);
+            }
+            self.saw.tick(self.frequency_shift);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    // the channels are summed unscaled, as on the cartridge: 15 + 15 + 31
+    fn audio_output(&self) -> f32 {
+        let sum = self.pulses[0].output() + self.pulses[1].output() + self.saw.output();
+        sum as f32 / 61.0
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        self.prg_16k = 0;
+        self.prg_8k = 0;
+        self.chr_banks = [0; 8];
+        self.ppu_mode = 0;
+        self.irq = VrcIrq::default();
+        self.pulses = [Pulse::default(), Pulse::default()];
+        self.saw = Saw::default();
+        self.halt = false;
+        self.frequency_shift = 0;
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.prg_16k.hash(&mut state);
+        self.prg_8k.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.ppu_mode.hash(&mut state);
+        self.irq.hash(&mut state);
+        self.pulses.hash(&mut state);
+        self.saw.hash(&mut state);
+        self.halt.hash(&mut state);
+        self.frequency_shift.hash(&mut state);
+    }
+}
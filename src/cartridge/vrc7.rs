@@ -0,0 +1,193 @@
+use std::hash::{Hash, Hasher};
+
+use super::opll::Opll;
+use super::vrc_irq::VrcIrq;
+use super::{InitPolicy, Mapper, Mirroring, Rom};
+
+// https://wiki.nesdev.org/w/index.php?title=VRC7
+//
+// Konami VRC7: three switchable 8KB PRG banks with the last fixed, eight 1KB CHR banks, the VRC
+// IRQ counter and, on the board Lagrange Point uses, an FM synthesizer. The second register of
+// each pair is selected by A4 on VRC7a and A3 on VRC7b.
+#[derive(Clone)]
+pub(super) struct Vrc7 {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+
+    // address line selecting the second register of a pair, both of them when unknown
+    select: u16,
+
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    // $E000
+    control: u8,
+    irq: VrcIrq,
+    opll: Opll,
+}
+
+impl Vrc7 {
+    pub(super) fn new(rom: Rom) -> Self {
+        let select = match rom.header.submapper {
+            1 => 0x08,
+            2 => 0x10,
+            _ => 0x18,
+        };
+        let (chr, chr_ram) = rom.chr();
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: vec![0; 0x2000],
+            chr,
+            chr_ram,
+            select,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::default(),
+            opll: Opll::new(),
+        }
+    }
+
+    // $x000 or $x010
+    fn register(&self, addr: u16) -> u16 {
+        addr & 0xF000 | ((addr & self.select != 0) as u16) << 4
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+
+    // bit 6 of $E000 holds the synthesizer in reset
+    fn audio_reset(&self) -> bool {
+        self.control & 0x40 != 0
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        let bank = match (addr - 0x8000) / 0x2000 {
+            n @ 0..=2 => self.prg_banks[n as usize] as usize,
+            _ => self.prg_rom.len() / 0x2000 - 1,
+        };
+        (bank * 0x2000 + (addr & 0x1FFF) as usize) % self.prg_rom.len()
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr >> 10) as usize & 7] as usize;
+        (bank * 0x400 + (addr & 0x3FF) as usize) % self.chr.len()
+    }
+}
+
+impl Mapper for Vrc7 {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => self.prg_rom[self.prg_addr(addr)],
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        // the synthesizer decodes A5 and A4 on its own
+        match addr & 0xF030 {
+            0x9010 => return self.opll.select(value),
+            0x9030 => {
+                if !self.audio_reset() {
+                    self.opll.write(value);
+                }
+                return;
+            }
+            _ => {}
+        }
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                self.prg_ram[(addr - 0x6000) as usize] = value
+            }
+            0x8000..=0xFFFF => match self.register(addr) {
+                0x8000 => self.prg_banks[0] = value & 0x3F,
+                0x8010 => self.prg_banks[1] = value & 0x3F,
+                0x9000 => self.prg_banks[2] = value & 0x3F,
+                r @ 0xA000..=0xD010 => {
+                    let i = ((r - 0xA000) >> 12) as usize * 2 + (r >> 4 & 1) as usize;
+                    self.chr_banks[i] = value;
+                }
+                0xE000 => {
+                    self.control = value;
+                    if self.audio_reset() {
+                        self.opll = Opll::new();
+                    }
+                }
+                0xE010 => self.irq.set_latch(value),
+                0xF000 => self.irq.write_control(value),
+                0xF010 => self.irq.acknowledge(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_addr(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8) {
+        if self.chr_ram {
+            let addr = self.chr_addr(addr);
+            self.chr[addr] = value;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        self.irq.tick();
+        if !self.audio_reset() {
+            self.opll.tick();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.opll.output()
+    }
+
+    fn power_on(&mut self, policy: InitPolicy, stream: u64) {
+        if self.chr_ram {
+            policy.fill(&mut self.chr, stream);
+        }
+        self.prg_banks = [0; 3];
+        self.chr_banks = [0; 8];
+        self.control = 0;
+        self.irq = VrcIrq::default();
+        self.opll = Opll::new();
+    }
+
+    fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_ram
+    }
+
+    fn hash_state(&self, mut state: &mut dyn Hasher) {
+        self.prg_ram.hash(&mut state);
+        if self.chr_ram {
+            self.chr.hash(&mut state);
+        }
+        self.prg_banks.hash(&mut state);
+        self.chr_banks.hash(&mut state);
+        self.control.hash(&mut state);
+        self.irq.hash(&mut state);
+        self.opll.hash(&mut state);
+    }
+}
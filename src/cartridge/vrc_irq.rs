@@ -0,0 +1,71 @@
+// IRQ counter of Konami VRC4, VRC6 and VRC7
+// https://wiki.nesdev.org/w/index.php?title=VRC_IRQ
+//
+// An 8-bit counter counting up to $FF, then reloaded from the latch. In scanline mode a prescaler
+// clocks it every 341/3 CPU cycles, the length of a scanline, without watching the PPU.
+
+#[derive(Debug, Default, Clone, Hash)]
+pub(super) struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    // enabled again by the acknowledge
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub(super) fn latch(&self) -> u8 {
+        self.latch
+    }
+
+    pub(super) fn set_latch(&mut self, value: u8) {
+        self.latch = value;
+    }
+
+    pub(super) fn write_control(&mut self, value: u8) {
+        self.enable_after_ack = value & 0x01 != 0;
+        self.enabled = value & 0x02 != 0;
+        self.cycle_mode = value & 0x04 != 0;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+        self.pending = false;
+    }
+
+    pub(super) fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    pub(super) fn pending(&self) -> bool {
+        self.pending
+    }
+
+    pub(super) fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.cycle_mode {
+            self.clock();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += 341;
+                self.clock();
+            }
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
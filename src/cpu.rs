@@ -0,0 +1,276 @@
+//! Generic 6502 core
+//!
+//! `Cpu` holds the register state only. Memory and devices are reached through a `Bus`, so the same
+//! core drives the NES or any other 6502 machine.
+
+mod addressing_mode;
+mod decode;
+mod dispatch;
+mod instruction;
+mod interrupt_handler;
+mod trace;
+
+/// CPU state
+#[derive(Debug, Default, Clone, Hash)]
+pub struct Cpu {
+    // https://wiki.nesdev.org/w/index.php?title=CPU_registers
+
+    // Accumulator, Index X/Y register
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    // Stack pointer
+    pub s: u8,
+
+    // Status register
+    pub p: Status,
+
+    // Program counter
+    pub pc: u16,
+
+    variant: Variant,
+
+    // 65C02 WAI/STP state
+    waiting: bool,
+    stopped: bool,
+}
+
+/// 6502 family members the core can behave as
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// NES CPU, a NMOS 6502 without decimal mode
+    #[default]
+    Ricoh2A03,
+    /// NMOS 6502
+    Nmos6502,
+    /// WDC 65C02
+    Cmos65C02,
+}
+
+impl Cpu {
+    pub fn new(variant: Variant) -> Self {
+        Self {
+            variant,
+            ..Self::default()
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Initializes the registers and jumps to the reset vector
+    pub fn power_on<B: Bus>(&mut self, bus: &mut B) {
+        bus.cpu_power_on(self);
+    }
+
+    /// Resets the CPU like the RESET line, keeping A, X and Y
+    pub fn reset<B: Bus>(&mut self, bus: &mut B) {
+        bus.cpu_reset(self);
+    }
+
+    /// Runs one instruction, handling a pending interrupt beforehand
+    pub fn step<B: Bus>(&mut self, bus: &mut B) {
+        bus.cpu_step(self);
+    }
+
+    fn incr_pc(&mut self, n: u16) {
+        self.pc = self.pc.wrapping_add(n);
+    }
+
+    // The 2A03 ignores the D flag
+    fn decimal_mode(&self) -> bool {
+        self.p.contains(Status::D) && self.variant != Variant::Ricoh2A03
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct Status: u8 {
+        // Negative
+        const N = 1 << 7;
+        // Overflow
+        const V = 1 << 6;
+        const R = 1 << 5;
+        const B = 1 << 4;
+        // Decimal mode
+        const D = 1 << 3;
+        // IRQ prevention
+        const I = 1 << 2;
+        // Zero
+        const Z = 1 << 1;
+        // Carry
+        const C = 1 << 0;
+        // B flags
+        // https://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+        const INTERRUPTED_B = 0b100000; // interruptB
+        const OPERATED_B = 0b110000; // instructionB
+    }
+}
+
+impl Status {
+    fn set_zn(&mut self, v: u8) {
+        self.set(Self::Z, v == 0);
+        self.set(Self::N, (v >> 7) & 1 == 1);
+    }
+}
+
+/// Kinds of CPU interrupts
+///
+/// It currently supports NMI and IRQ only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Interrupt {
+    NMI,
+    IRQ,
+}
+
+/// Everything the CPU is connected to
+///
+/// Every CPU cycle performs exactly one `cpu_read` or `cpu_write` followed by `on_cpu_tick`.
+pub trait Bus {
+    fn cpu_read(&mut self, addr: u16) -> u8;
+    fn cpu_write(&mut self, addr: u16, value: u8);
+
+    fn on_cpu_tick(&mut self) {}
+
+    /// Polls the interrupt lines before each instruction
+    ///
+    /// NMI is edge-triggered, so it should be reported once per request. IRQ is level-triggered and
+    /// should be reported as long as it is asserted.
+    fn poll_interrupt(&mut self) -> Option<Interrupt> {
+        None
+    }
+}
+
+trait Emu {
+    fn cpu_power_on(&mut self, cpu: &mut Cpu);
+    fn cpu_reset(&mut self, cpu: &mut Cpu);
+    fn cpu_step(&mut self, cpu: &mut Cpu);
+}
+
+trait EmuImpl: Bus {
+    fn fetch(&mut self, cpu: &mut Cpu) -> u8 {
+        let op = self.read(cpu.pc);
+        cpu.pc = cpu.pc.wrapping_add(1);
+        op
+    }
+
+    fn read(&mut self, addr: u16) -> u8 {
+        let v = self.cpu_read(addr);
+        self.tick();
+        v
+    }
+
+    fn read_word(&mut self, addr: u16) -> u16 {
+        let low = self.read(addr) as u16;
+        let high = self.read(addr.wrapping_add(1)) as u16;
+        low | (high << 8)
+    }
+
+    fn read_on_indirect(&mut self, addr: u16) -> u16 {
+        let low = self.read(addr) as u16;
+        // Reproduce 6502 bug - http://nesdev.com/6502bugs.txt
+        let high = self.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF)) as u16;
+        low | (high << 8)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.cpu_write(addr, value);
+        self.tick();
+    }
+
+    fn tick(&mut self) {
+        self.on_cpu_tick();
+    }
+
+    fn push_stack(&mut self, cpu: &mut Cpu, v: u8) {
+        self.write(cpu.s as u16 + 0x0100, v);
+        cpu.s = cpu.s.wrapping_sub(1);
+    }
+
+    fn push_stack_word(&mut self, cpu: &mut Cpu, v: u16) {
+        self.push_stack(cpu, (v >> 8) as u8);
+        self.push_stack(cpu, (v & 0xFF) as u8);
+    }
+
+    fn pull_stack(&mut self, cpu: &mut Cpu) -> u8 {
+        cpu.s = cpu.s.wrapping_add(1);
+        self.read(cpu.s as u16 + 0x0100)
+    }
+
+    fn pull_stack_word(&mut self, cpu: &mut Cpu) -> u16 {
+        self.pull_stack(cpu) as u16 | (self.pull_stack(cpu) as u16) << 8
+    }
+}
+
+impl<B: Bus> EmuImpl for B {}
+
+impl<T: EmuImpl> Emu for T {
+    fn cpu_power_on(&mut self, cpu: &mut Cpu) {
+        // https://wiki.nesdev.com/w/index.php/CPU_power_up_state
+
+        // IRQ disabled
+        cpu.p = Status::from_bits_truncate(0x34);
+        cpu.a = 0x00;
+        cpu.x = 0x00;
+        cpu.y = 0x00;
+        cpu.s = 0xFD;
+        cpu.pc = self.read_word(0xFFFC);
+        cpu.waiting = false;
+        cpu.stopped = false;
+    }
+
+    fn cpu_reset(&mut self, cpu: &mut Cpu) {
+        // https://wiki.nesdev.com/w/index.php/CPU_power_up_state#After_reset
+
+        // Runs like an interrupt whose pushes are turned into reads
+        self.read(cpu.pc);
+        self.read(cpu.pc);
+        for _ in 0..3 {
+            self.read(cpu.s as u16 + 0x0100);
+            cpu.s = cpu.s.wrapping_sub(1);
+        }
+        cpu.p.insert(Status::I);
+        if cpu.variant == Variant::Cmos65C02 {
+            cpu.p.remove(Status::D);
+        }
+        cpu.pc = self.read_word(0xFFFC);
+        cpu.waiting = false;
+        cpu.stopped = false;
+    }
+
+    fn cpu_step(&mut self, cpu: &mut Cpu) {
+        use dispatch::Dispatch;
+        use interrupt_handler::InterruptHandler;
+
+        // Stopped and waiting CPUs keep reading the next opcode
+        if cpu.stopped {
+            self.read(cpu.pc);
+            return;
+        }
+
+        let interrupt = self.poll_interrupt();
+        if cpu.waiting {
+            if interrupt.is_none() {
+                self.read(cpu.pc);
+                return;
+            }
+            // WAI resumes on any interrupt, even if IRQ is masked
+            cpu.waiting = false;
+        }
+        self.handle_interrupt(cpu, interrupt);
+
+        let op = self.fetch(cpu);
+
+        let handlers = match cpu.variant {
+            Variant::Cmos65C02 => &Dispatch::<Self, true>::HANDLERS,
+            _ => &Dispatch::<Self, false>::HANDLERS,
+        };
+        handlers[op as usize](self, cpu);
+    }
+}
+
+fn page_crossed(a: u16, b: u16) -> bool {
+    a.wrapping_add(b) & 0xFF00 != (b & 0xFF00)
+}
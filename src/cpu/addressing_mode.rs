@@ -0,0 +1,136 @@
+use super::{page_crossed, Cpu, EmuImpl, Variant};
+
+// 6502 addressing modes
+/// https://wiki.nesdev.org/w/index.php?title=CPU_addressing_modes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub enum AddressingMode {
+    Implicit,
+    Accumulator,
+    Immediate,
+    ZeroPage, ZeroPageX, ZeroPageY,
+    Absolute,
+    AbsoluteX { penalty: bool },
+    AbsoluteY { penalty: bool },
+    Relative,
+    Indirect, IndexedIndirect, IndirectIndexed { penalty: bool },
+    // 65C02
+    ZeroPageIndirect, AbsoluteIndexedIndirect, ZeroPageRelative,
+}
+
+pub(super) trait GetOperand {
+    fn get_operand(&mut self, cpu: &mut Cpu, mode: AddressingMode) -> u16;
+}
+
+impl<T: EmuImpl> GetOperand for T {
+    #[inline(always)]
+    fn get_operand(&mut self, cpu: &mut Cpu, mode: AddressingMode) -> u16 {
+        use AddressingMode::*;
+
+        match mode {
+            Implicit => {
+                // dummy read of the next byte
+                self.read(cpu.pc);
+                0
+            }
+            Accumulator => {
+                self.read(cpu.pc);
+                cpu.a as u16
+            }
+            Immediate => {
+                let pc = cpu.pc;
+                cpu.incr_pc(1);
+                pc
+            }
+            ZeroPage | ZeroPageRelative => {
+                let v = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                v as u16
+            }
+            ZeroPageX => {
+                let v = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                self.read(v as u16);
+                v.wrapping_add(cpu.x) as u16
+            }
+            ZeroPageY => {
+                let v = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                self.read(v as u16);
+                v.wrapping_add(cpu.y) as u16
+            }
+            Absolute => {
+                let v = self.read_word(cpu.pc);
+                cpu.incr_pc(2);
+                v
+            }
+            AbsoluteX { penalty } => {
+                let v = self.read_word(cpu.pc);
+                cpu.incr_pc(2);
+                indexed(self, cpu, v, cpu.x, penalty)
+            }
+            AbsoluteY { penalty } => {
+                let v = self.read_word(cpu.pc);
+                cpu.incr_pc(2);
+                indexed(self, cpu, v, cpu.y, penalty)
+            }
+            Relative => {
+                let v = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                v as u16
+            }
+            Indirect => {
+                let m = self.read_word(cpu.pc);
+                cpu.incr_pc(2);
+                if cpu.variant == Variant::Cmos65C02 {
+                    // Fixed to read the high byte from the next page, taking an extra cycle
+                    self.read(cpu.pc.wrapping_sub(1));
+                    self.read_word(m)
+                } else {
+                    self.read_on_indirect(m)
+                }
+            }
+            IndexedIndirect => {
+                let m = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                self.read(m as u16);
+                self.read_on_indirect(m.wrapping_add(cpu.x) as u16)
+            }
+            IndirectIndexed { penalty } => {
+                let m = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                let v = self.read_on_indirect(m as u16);
+                indexed(self, cpu, v, cpu.y, penalty)
+            }
+            ZeroPageIndirect => {
+                let m = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                self.read_on_indirect(m as u16)
+            }
+            AbsoluteIndexedIndirect => {
+                let m = self.read_word(cpu.pc);
+                cpu.incr_pc(2);
+                self.read(cpu.pc.wrapping_sub(1));
+                self.read_word(m.wrapping_add(cpu.x as u16))
+            }
+        }
+    }
+}
+
+// Adds an index register to a base address.
+//
+// The 6502 reads from the address before fixing up its high byte, so that read takes an extra cycle
+// when a page is crossed, or always for instructions which write to the address (`penalty: false`).
+// The 65C02 reads the last operand byte again instead.
+fn indexed<E: EmuImpl>(e: &mut E, cpu: &Cpu, base: u16, index: u8, penalty: bool) -> u16 {
+    let v = base.wrapping_add(index as u16);
+    if !penalty || page_crossed(index as u16, base) {
+        if cpu.variant == Variant::Cmos65C02 {
+            e.read(cpu.pc.wrapping_sub(1));
+        } else {
+            e.read((base & 0xFF00) | (v & 0x00FF));
+        }
+    }
+    v
+}
@@ -1,7 +1,88 @@
 use super::addressing_mode::AddressingMode;
 use super::instruction::Instruction;
+use super::Variant;
 
-pub(crate) fn decode(opcode: u8) -> (Instruction, AddressingMode) {
+pub(crate) const fn decode(variant: Variant, opcode: u8) -> (Instruction, AddressingMode) {
+    match variant {
+        Variant::Cmos65C02 => decode_cmos(opcode),
+        _ => decode_nmos(opcode),
+    }
+}
+
+// WDC 65C02 replaces all unofficial opcodes with new instructions or NOPs
+// http://6502.org/tutorials/65c02opcodes.html
+const fn decode_cmos(opcode: u8) -> (Instruction, AddressingMode) {
+    use super::addressing_mode::AddressingMode::*;
+    use super::instruction::Instruction::*;
+
+    match opcode {
+        0x80 => (BRA, Relative),
+
+        0xDA => (PHX, Implicit),
+        0x5A => (PHY, Implicit),
+        0xFA => (PLX, Implicit),
+        0x7A => (PLY, Implicit),
+
+        0x64 => (STZ, ZeroPage),
+        0x74 => (STZ, ZeroPageX),
+        0x9C => (STZ, Absolute),
+        0x9E => (STZ, AbsoluteX { penalty: false }),
+
+        0x14 => (TRB, ZeroPage),
+        0x1C => (TRB, Absolute),
+        0x04 => (TSB, ZeroPage),
+        0x0C => (TSB, Absolute),
+
+        0x12 => (ORA, ZeroPageIndirect),
+        0x32 => (AND, ZeroPageIndirect),
+        0x52 => (EOR, ZeroPageIndirect),
+        0x72 => (ADC, ZeroPageIndirect),
+        0x92 => (STA, ZeroPageIndirect),
+        0xB2 => (LDA, ZeroPageIndirect),
+        0xD2 => (CMP, ZeroPageIndirect),
+        0xF2 => (SBC, ZeroPageIndirect),
+
+        0x89 => (BIT, Immediate),
+        0x34 => (BIT, ZeroPageX),
+        0x3C => (BIT, AbsoluteX { penalty: true }),
+
+        0x1A => (INC, Accumulator),
+        0x3A => (DEC, Accumulator),
+
+        0x7C => (JMP, AbsoluteIndexedIndirect),
+
+        // Shifts take an extra cycle only when a page is crossed
+        0x1E => (ASL, AbsoluteX { penalty: true }),
+        0x5E => (LSR, AbsoluteX { penalty: true }),
+        0x3E => (ROL, AbsoluteX { penalty: true }),
+        0x7E => (ROR, AbsoluteX { penalty: true }),
+
+        0x07 | 0x17 | 0x27 | 0x37 | 0x47 | 0x57 | 0x67 | 0x77 => (RMB(opcode >> 4), ZeroPage),
+        0x87 | 0x97 | 0xA7 | 0xB7 | 0xC7 | 0xD7 | 0xE7 | 0xF7 => (SMB((opcode >> 4) & 7), ZeroPage),
+        0x0F | 0x1F | 0x2F | 0x3F | 0x4F | 0x5F | 0x6F | 0x7F => {
+            (BBR(opcode >> 4), ZeroPageRelative)
+        }
+        0x8F | 0x9F | 0xAF | 0xBF | 0xCF | 0xDF | 0xEF | 0xFF => {
+            (BBS((opcode >> 4) & 7), ZeroPageRelative)
+        }
+
+        0xCB => (WAI, Implicit),
+        0xDB => (STP, Implicit),
+
+        // Reserved
+        0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 => (NOP, Immediate),
+        0x44 => (NOP, ZeroPage),
+        0x54 | 0xD4 | 0xF4 => (NOP, ZeroPageX),
+        // $5C takes 8 cycles on real chips
+        0x5C | 0xDC | 0xFC => (NOP, Absolute),
+        // $x3 and $xB, see `dispatch::execute_op`
+        _ if opcode & 0x07 == 0x03 => (NOP, Implicit),
+
+        _ => decode_nmos(opcode),
+    }
+}
+
+const fn decode_nmos(opcode: u8) -> (Instruction, AddressingMode) {
     use super::addressing_mode::AddressingMode::*;
     use super::instruction::Instruction::*;
 
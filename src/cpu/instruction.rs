@@ -0,0 +1,567 @@
+use super::addressing_mode::AddressingMode;
+use super::{Cpu, EmuImpl, Status, Variant};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[rustfmt::skip]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum Instruction {
+    // Load/Store Operations
+    LDA, LDX, LDY, STA, STX, STY,
+    // Register Operations
+    TAX, TSX, TAY, TXA, TXS, TYA,
+    // Stack instructions
+    PHA, PHP, PLA, PLP,
+    // Logical instructions
+    AND, EOR, ORA, BIT,
+    // Arithmetic instructions
+    ADC, SBC, CMP, CPX, CPY,
+    // Increment/Decrement instructions
+    INC, INX, INY, DEC, DEX, DEY,
+    // Shift instructions
+    ASL, LSR, ROL, ROR,
+    // Jump instructions
+    JMP, JSR, RTS, RTI,
+    // Branch instructions
+    BCC, BCS, BEQ, BMI, BNE, BPL, BVC, BVS,
+    // Flag control instructions
+    CLC, CLD, CLI, CLV, SEC, SED, SEI,
+    // Misc
+    BRK, NOP,
+    // Unofficial
+    LAX, SAX, DCP, ISB, SLO, RLA, SRE, RRA,
+    // 65C02
+    BRA, PHX, PHY, PLX, PLY, STZ, TRB, TSB, WAI, STP,
+    // 65C02 bit instructions with the bit number
+    RMB(u8), SMB(u8), BBR(u8), BBS(u8),
+}
+
+pub(super) trait ExecuteInstruction {
+    fn execute(&mut self, cpu: &mut Cpu, inst: (Instruction, AddressingMode), operand: u16);
+}
+
+impl<T: EmuImpl> ExecuteInstruction for T {
+    #[inline(always)]
+    fn execute(
+        &mut self,
+        cpu: &mut Cpu,
+        (instruction, mode): (Instruction, AddressingMode),
+        operand: u16,
+    ) {
+        use Instruction::*;
+
+        match instruction {
+            LDA => {
+                cpu.a = self.read(operand);
+                cpu.p.set_zn(cpu.a);
+            }
+            LDX => {
+                cpu.x = self.read(operand);
+                cpu.p.set_zn(cpu.x);
+            }
+            LDY => {
+                cpu.y = self.read(operand);
+                cpu.p.set_zn(cpu.y);
+            }
+            STA => {
+                self.write(operand, cpu.a);
+            }
+            STX => {
+                self.write(operand, cpu.x);
+            }
+            STY => {
+                self.write(operand, cpu.y);
+            }
+            TAX => {
+                cpu.x = cpu.a;
+                cpu.p.set_zn(cpu.x);
+            }
+            TAY => {
+                cpu.y = cpu.a;
+                cpu.p.set_zn(cpu.y);
+            }
+            TXA => {
+                cpu.a = cpu.x;
+                cpu.p.set_zn(cpu.a);
+            }
+            TYA => {
+                cpu.a = cpu.y;
+                cpu.p.set_zn(cpu.a);
+            }
+            TSX => {
+                cpu.x = cpu.s;
+                cpu.p.set_zn(cpu.x);
+            }
+            TXS => {
+                cpu.s = cpu.x;
+            }
+            PHA => {
+                self.push_stack(cpu, cpu.a);
+            }
+            PHP => {
+                let p = cpu.p.bits | Status::OPERATED_B.bits;
+                self.push_stack(cpu, p);
+            }
+            PLA => {
+                self.read(cpu.s as u16 + 0x0100);
+                cpu.a = self.pull_stack(cpu);
+                cpu.p.set_zn(cpu.a);
+            }
+            PLP => {
+                self.read(cpu.s as u16 + 0x0100);
+                let v = self.pull_stack(cpu);
+                cpu.p = pulled_status(v);
+            }
+            AND => {
+                let m = self.read(operand);
+                and(cpu, m);
+            }
+            EOR => {
+                let m = self.read(operand);
+                eor(cpu, m);
+            }
+            ORA => {
+                let m = self.read(operand);
+                ora(cpu, m);
+            }
+            BIT => {
+                let m = self.read(operand);
+                let b = cpu.a & m;
+                cpu.p.set(Status::Z, b == 0);
+                // 65C02 BIT #imm affects Z only
+                if mode != AddressingMode::Immediate {
+                    cpu.p.set(Status::V, m & 0x40 == 0x40);
+                    cpu.p.set(Status::N, m & 0x80 == 0x80);
+                }
+            }
+            ADC => {
+                let m = self.read(operand);
+                if cpu.decimal_mode() && cpu.variant == Variant::Cmos65C02 {
+                    self.read(cpu.pc);
+                }
+                adc(cpu, m);
+            }
+            SBC => {
+                let m = self.read(operand);
+                if cpu.decimal_mode() && cpu.variant == Variant::Cmos65C02 {
+                    self.read(cpu.pc);
+                }
+                sbc(cpu, m);
+            }
+            CMP => {
+                let m = self.read(operand);
+                cmp(cpu, cpu.a, m);
+            }
+            CPX => {
+                let m = self.read(operand);
+                cmp(cpu, cpu.x, m);
+            }
+            CPY => {
+                let m = self.read(operand);
+                cmp(cpu, cpu.y, m);
+            }
+
+            INC => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = cpu.a.wrapping_add(1);
+                    cpu.p.set_zn(cpu.a);
+                } else {
+                    let r = modify(self, cpu, operand, |_, m| m.wrapping_add(1));
+                    cpu.p.set_zn(r);
+                }
+            }
+            INX => {
+                cpu.x = cpu.x.wrapping_add(1);
+                cpu.p.set_zn(cpu.x);
+            }
+            INY => {
+                cpu.y = cpu.y.wrapping_add(1);
+                cpu.p.set_zn(cpu.y);
+            }
+            DEC => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = cpu.a.wrapping_sub(1);
+                    cpu.p.set_zn(cpu.a);
+                } else {
+                    let r = modify(self, cpu, operand, |_, m| m.wrapping_sub(1));
+                    cpu.p.set_zn(r);
+                }
+            }
+            DEX => {
+                cpu.x = cpu.x.wrapping_sub(1);
+                cpu.p.set_zn(cpu.x);
+            }
+            DEY => {
+                cpu.y = cpu.y.wrapping_sub(1);
+                cpu.p.set_zn(cpu.y);
+            }
+            ASL => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = asl(cpu, cpu.a);
+                } else {
+                    modify(self, cpu, operand, asl);
+                }
+            }
+            LSR => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = lsr(cpu, cpu.a);
+                } else {
+                    modify(self, cpu, operand, lsr);
+                }
+            }
+            ROL => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = rol(cpu, cpu.a);
+                } else {
+                    modify(self, cpu, operand, rol);
+                }
+            }
+            ROR => {
+                if mode == AddressingMode::Accumulator {
+                    cpu.a = ror(cpu, cpu.a);
+                } else {
+                    modify(self, cpu, operand, ror);
+                }
+            }
+            JMP => {
+                cpu.pc = operand;
+            }
+            JSR => {
+                let low = self.read(cpu.pc) as u16;
+                cpu.incr_pc(1);
+                self.read(cpu.s as u16 + 0x0100);
+                self.push_stack_word(cpu, cpu.pc);
+                let high = self.read(cpu.pc) as u16;
+                cpu.pc = low | (high << 8);
+            }
+            RTS => {
+                self.read(cpu.s as u16 + 0x0100);
+                cpu.pc = self.pull_stack_word(cpu);
+                self.read(cpu.pc);
+                cpu.incr_pc(1);
+            }
+
+            BCC => branch(self, cpu, operand, !cpu.p.contains(Status::C)),
+            BCS => branch(self, cpu, operand, cpu.p.contains(Status::C)),
+            BEQ => branch(self, cpu, operand, cpu.p.contains(Status::Z)),
+            BMI => branch(self, cpu, operand, cpu.p.contains(Status::N)),
+            BNE => branch(self, cpu, operand, !cpu.p.contains(Status::Z)),
+            BPL => branch(self, cpu, operand, !cpu.p.contains(Status::N)),
+            BVC => branch(self, cpu, operand, !cpu.p.contains(Status::V)),
+            BVS => branch(self, cpu, operand, cpu.p.contains(Status::V)),
+
+            CLC => cpu.p.remove(Status::C),
+            CLD => cpu.p.remove(Status::D),
+            CLI => cpu.p.remove(Status::I),
+            CLV => cpu.p.remove(Status::V),
+            SEC => cpu.p.insert(Status::C),
+            SED => cpu.p.insert(Status::D),
+            SEI => cpu.p.insert(Status::I),
+
+            BRK => {
+                // skip the padding byte
+                cpu.incr_pc(1);
+                self.push_stack_word(cpu, cpu.pc);
+                self.push_stack(cpu, cpu.p.bits | Status::OPERATED_B.bits);
+                cpu.p.insert(Status::I);
+                if cpu.variant == Variant::Cmos65C02 {
+                    cpu.p.remove(Status::D);
+                }
+                cpu.pc = self.read_word(0xFFFE);
+            }
+            NOP => {
+                if mode != AddressingMode::Implicit {
+                    self.read(operand);
+                }
+            }
+            RTI => {
+                self.read(cpu.s as u16 + 0x0100);
+                let v = self.pull_stack(cpu);
+                cpu.p = pulled_status(v);
+                cpu.pc = self.pull_stack_word(cpu);
+            }
+
+            LAX => {
+                let m = self.read(operand);
+                cpu.a = m;
+                cpu.p.set_zn(m);
+                cpu.x = m;
+            }
+            SAX => self.write(operand, cpu.a & cpu.x),
+            DCP => {
+                let r = modify(self, cpu, operand, |_, m| m.wrapping_sub(1));
+                cmp(cpu, cpu.a, r);
+            }
+            ISB => {
+                let r = modify(self, cpu, operand, |_, m| m.wrapping_add(1));
+                sbc(cpu, r);
+            }
+            SLO => {
+                let r = modify(self, cpu, operand, asl);
+                ora(cpu, r);
+            }
+            RLA => {
+                let r = modify(self, cpu, operand, rol);
+                and(cpu, r);
+            }
+            SRE => {
+                let r = modify(self, cpu, operand, lsr);
+                eor(cpu, r);
+            }
+            RRA => {
+                let r = modify(self, cpu, operand, ror);
+                adc(cpu, r);
+            }
+
+            BRA => branch(self, cpu, operand, true),
+            PHX => {
+                self.push_stack(cpu, cpu.x);
+            }
+            PHY => {
+                self.push_stack(cpu, cpu.y);
+            }
+            PLX => {
+                self.read(cpu.s as u16 + 0x0100);
+                cpu.x = self.pull_stack(cpu);
+                cpu.p.set_zn(cpu.x);
+            }
+            PLY => {
+                self.read(cpu.s as u16 + 0x0100);
+                cpu.y = self.pull_stack(cpu);
+                cpu.p.set_zn(cpu.y);
+            }
+            STZ => self.write(operand, 0),
+            TRB => {
+                modify(self, cpu, operand, |cpu, m| {
+                    cpu.p.set(Status::Z, cpu.a & m == 0);
+                    m & !cpu.a
+                });
+            }
+            TSB => {
+                modify(self, cpu, operand, |cpu, m| {
+                    cpu.p.set(Status::Z, cpu.a & m == 0);
+                    m | cpu.a
+                });
+            }
+            WAI => {
+                self.read(cpu.pc);
+                cpu.waiting = true;
+            }
+            STP => {
+                self.read(cpu.pc);
+                cpu.stopped = true;
+            }
+            RMB(n) => {
+                modify(self, cpu, operand, |_, m| m & !(1 << n));
+            }
+            SMB(n) => {
+                modify(self, cpu, operand, |_, m| m | (1 << n));
+            }
+            BBR(n) | BBS(n) => {
+                let m = self.read(operand);
+                self.read(operand);
+                let offset = self.read(cpu.pc);
+                cpu.incr_pc(1);
+                let set = m & (1 << n) != 0;
+                branch(
+                    self,
+                    cpu,
+                    offset as u16,
+                    set == matches!(instruction, BBS(_)),
+                );
+            }
+        }
+    }
+}
+
+// Read-modify-write instructions write the unmodified value back before writing the result.
+// The 65C02 reads it again instead.
+fn modify<E: EmuImpl>(e: &mut E, cpu: &mut Cpu, addr: u16, f: impl Fn(&mut Cpu, u8) -> u8) -> u8 {
+    let m = e.read(addr);
+    if cpu.variant == Variant::Cmos65C02 {
+        e.read(addr);
+    } else {
+        e.write(addr, m);
+    }
+    let r = f(cpu, m);
+    e.write(addr, r);
+    r
+}
+
+// B flags don't exist in the status register
+// https://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+fn pulled_status(v: u8) -> Status {
+    let mut p = Status::from_bits_truncate(v);
+    p.remove(Status::B);
+    p.insert(Status::R);
+    p
+}
+
+fn and(cpu: &mut Cpu, m: u8) {
+    cpu.a &= m;
+    cpu.p.set_zn(cpu.a);
+}
+
+fn eor(cpu: &mut Cpu, m: u8) {
+    cpu.a ^= m;
+    cpu.p.set_zn(cpu.a);
+}
+
+fn ora(cpu: &mut Cpu, m: u8) {
+    cpu.a |= m;
+    cpu.p.set_zn(cpu.a);
+}
+
+fn carry(cpu: &mut Cpu, m: u8, r: u8) {
+    let a7 = cpu.a >> 7 & 1;
+    let m7 = m >> 7 & 1;
+    let c6 = a7 ^ m7 ^ (r >> 7 & 1);
+    let c7 = (a7 & m7) | (a7 & c6) | (m7 & c6);
+    cpu.p.set(Status::C, c7 == 1);
+    cpu.p.set(Status::V, c6 ^ c7 == 1);
+}
+
+fn add(cpu: &mut Cpu, m: u8) -> u8 {
+    let mut r = cpu.a.wrapping_add(m);
+    if cpu.p.contains(Status::C) {
+        r = r.wrapping_add(1);
+    }
+    carry(cpu, m, r);
+    r
+}
+
+fn adc(cpu: &mut Cpu, m: u8) {
+    if cpu.decimal_mode() {
+        return adc_decimal(cpu, m);
+    }
+    cpu.a = add(cpu, m);
+    cpu.p.set_zn(cpu.a);
+}
+
+fn sbc(cpu: &mut Cpu, m: u8) {
+    if cpu.decimal_mode() {
+        return sbc_decimal(cpu, m);
+    }
+    cpu.a = add(cpu, !m);
+    cpu.p.set_zn(cpu.a);
+}
+
+// http://www.6502.org/tutorials/decimal_mode.html#A
+fn adc_decimal(cpu: &mut Cpu, m: u8) {
+    let (a, b) = (cpu.a as i16, m as i16);
+    let c = cpu.p.contains(Status::C) as i16;
+
+    let mut al = (a & 0x0F) + (b & 0x0F) + c;
+    if al >= 0x0A {
+        al = ((al + 0x06) & 0x0F) + 0x10;
+    }
+    // N and V are determined before adjusting the high digit
+    let r = (a & 0xF0) as u8 as i8 as i16 + (b & 0xF0) as u8 as i8 as i16 + al;
+    cpu.p.set(Status::V, !(-128..=127).contains(&r));
+    let n = r & 0x80 == 0x80;
+
+    let mut r = (a & 0xF0) + (b & 0xF0) + al;
+    if r >= 0xA0 {
+        r += 0x60;
+    }
+    cpu.p.set(Status::C, r >= 0x100);
+    cpu.a = r as u8;
+
+    if cpu.variant == Variant::Cmos65C02 {
+        cpu.p.set_zn(cpu.a);
+    } else {
+        // Z is the same as in binary mode
+        cpu.p.set(Status::N, n);
+        cpu.p.set(Status::Z, (a + b + c) & 0xFF == 0);
+    }
+}
+
+fn sbc_decimal(cpu: &mut Cpu, m: u8) {
+    let (a, b) = (cpu.a as i16, m as i16);
+    let c = cpu.p.contains(Status::C) as i16;
+    // C and V are the same as in binary mode
+    let binary = add(cpu, !m);
+
+    let mut al = (a & 0x0F) - (b & 0x0F) + c - 1;
+    let r = if cpu.variant == Variant::Cmos65C02 {
+        let mut r = a - b + c - 1;
+        if r < 0 {
+            r -= 0x60;
+        }
+        if al < 0 {
+            r -= 0x06;
+        }
+        r
+    } else {
+        if al < 0 {
+            al = ((al - 0x06) & 0x0F) - 0x10;
+        }
+        let mut r = (a & 0xF0) - (b & 0xF0) + al;
+        if r < 0 {
+            r -= 0x60;
+        }
+        r
+    };
+    cpu.a = r as u8;
+
+    if cpu.variant == Variant::Cmos65C02 {
+        cpu.p.set_zn(cpu.a);
+    } else {
+        cpu.p.set_zn(binary);
+    }
+}
+
+fn cmp(cpu: &mut Cpu, x: u8, m: u8) {
+    let r = x as i16 - m as i16;
+    cpu.p.set_zn(r as u8);
+    cpu.p.set(Status::C, 0 <= r);
+}
+
+fn asl(cpu: &mut Cpu, m: u8) -> u8 {
+    cpu.p.set(Status::C, m & 0x80 == 0x80);
+    let r = m << 1;
+    cpu.p.set_zn(r);
+    r
+}
+
+fn lsr(cpu: &mut Cpu, m: u8) -> u8 {
+    cpu.p.set(Status::C, m & 1 == 1);
+    let r = m >> 1;
+    cpu.p.set_zn(r);
+    r
+}
+
+fn rol(cpu: &mut Cpu, m: u8) -> u8 {
+    let c = m & 0x80;
+    let mut r = m << 1;
+    if cpu.p.contains(Status::C) {
+        r |= 1;
+    }
+    cpu.p.set(Status::C, c == 0x80);
+    cpu.p.set_zn(r);
+    r
+}
+
+fn ror(cpu: &mut Cpu, m: u8) -> u8 {
+    let c = m & 1;
+    let mut r = m >> 1;
+    if cpu.p.contains(Status::C) {
+        r |= 0x80;
+    }
+    cpu.p.set(Status::C, c == 1);
+    cpu.p.set_zn(r);
+    r
+}
+
+fn branch<E: EmuImpl>(e: &mut E, cpu: &mut Cpu, v: u16, cond: bool) {
+    if !cond {
+        return;
+    }
+    e.read(cpu.pc);
+    let base = cpu.pc;
+    let offset = v as u8 as i8; // to negative number
+    let pc = base.wrapping_add(offset as u16);
+    if pc & 0xFF00 != base & 0xFF00 {
+        e.read((base & 0xFF00) | (pc & 0x00FF));
+    }
+    cpu.pc = pc;
+}
@@ -0,0 +1,29 @@
+use super::{Cpu, EmuImpl, Interrupt, Status, Variant};
+
+pub(super) trait InterruptHandler {
+    fn handle_interrupt(&mut self, cpu: &mut Cpu, interrupt: Option<Interrupt>);
+}
+
+impl<T: EmuImpl> InterruptHandler for T {
+    fn handle_interrupt(&mut self, cpu: &mut Cpu, interrupt: Option<Interrupt>) {
+        match interrupt {
+            Some(Interrupt::NMI) => on_interrupt(self, cpu, 0xFFFA),
+            Some(Interrupt::IRQ) if !cpu.p.contains(Status::I) => on_interrupt(self, cpu, 0xFFFE),
+            _ => {}
+        }
+    }
+}
+
+fn on_interrupt<E: EmuImpl>(e: &mut E, cpu: &mut Cpu, vector: u16) {
+    e.read(cpu.pc);
+    e.read(cpu.pc);
+    e.push_stack_word(cpu, cpu.pc);
+    // https://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+    // http://visual6502.org/wiki/index.php?title=6502_BRK_and_B_bit
+    e.push_stack(cpu, cpu.p.bits | Status::INTERRUPTED_B.bits);
+    cpu.p.insert(Status::I);
+    if cpu.variant == Variant::Cmos65C02 {
+        cpu.p.remove(Status::D);
+    }
+    cpu.pc = e.read_word(vector);
+}
@@ -0,0 +1,74 @@
+use super::addressing_mode::AddressingMode;
+use super::decode::decode;
+use super::instruction::Instruction;
+use super::Cpu;
+
+impl Cpu {
+    /// Disassembles the instruction at PC and formats it with the registers like nestest.log
+    ///
+    /// `peek` reads memory without side effects.
+    pub fn trace(&self, peek: impl Fn(u16) -> u8) -> String {
+        use AddressingMode::*;
+
+        let pc = self.pc;
+        let byte = |n: u16| peek(pc.wrapping_add(n));
+        let (inst, mode) = decode(self.variant, byte(0));
+
+        let len = match mode {
+            Implicit | Accumulator => 1,
+            Absolute
+            | AbsoluteX { .. }
+            | AbsoluteY { .. }
+            | Indirect
+            | AbsoluteIndexedIndirect
+            | ZeroPageRelative => 3,
+            _ => 2,
+        };
+        let bytes = (0..len)
+            .map(|n| format!("{:02X}", byte(n)))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let zp = byte(1);
+        let abs = byte(1) as u16 | (byte(2) as u16) << 8;
+        let relative = |offset: u8| pc.wrapping_add(len).wrapping_add(offset as i8 as u16);
+        let operand = match mode {
+            Implicit => String::new(),
+            Accumulator => "A".to_string(),
+            Immediate => format!("#${:02X}", zp),
+            ZeroPage => format!("${:02X}", zp),
+            ZeroPageX => format!("${:02X},X", zp),
+            ZeroPageY => format!("${:02X},Y", zp),
+            Absolute => format!("${:04X}", abs),
+            AbsoluteX { .. } => format!("${:04X},X", abs),
+            AbsoluteY { .. } => format!("${:04X},Y", abs),
+            Relative => format!("${:04X}", relative(zp)),
+            Indirect => format!("(${:04X})", abs),
+            IndexedIndirect => format!("(${:02X},X)", zp),
+            IndirectIndexed { .. } => format!("(${:02X}),Y", zp),
+            ZeroPageIndirect => format!("(${:02X})", zp),
+            AbsoluteIndexedIndirect => format!("(${:04X},X)", abs),
+            ZeroPageRelative => format!("${:02X},${:04X}", zp, relative(byte(2))),
+        };
+
+        let mnemonic = match inst {
+            Instruction::RMB(n) => format!("RMB{}", n),
+            Instruction::SMB(n) => format!("SMB{}", n),
+            Instruction::BBR(n) => format!("BBR{}", n),
+            Instruction::BBS(n) => format!("BBS{}", n),
+            _ => format!("{:?}", inst),
+        };
+
+        format!(
+            "{:04X}  {:<8}  {:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            pc,
+            bytes,
+            format!("{} {}", mnemonic, operand).trim_end(),
+            self.a,
+            self.x,
+            self.y,
+            self.p.bits(),
+            self.s,
+        )
+    }
+}
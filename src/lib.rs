@@ -1,4 +1,13 @@
 #[macro_use]
 extern crate bitflags;
 
+pub mod cartridge;
+pub mod cpu;
+mod fnv;
+mod md5;
+pub mod movie;
 mod nes;
+pub mod ntsc;
+pub mod palette;
+
+pub use nes::{Buttons, InitPolicy, Nes};
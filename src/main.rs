@@ -0,0 +1,284 @@
+//! Headless runner
+//!
+//! Runs a ROM without a GUI until a number of frames has passed or a stop condition is met, so
+//! regression ROMs can be run from CI.
+
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use korones::cartridge::{Cartridge, FileStorage};
+use korones::movie::{Movie, Player};
+use korones::{InitPolicy, Nes};
+
+const USAGE: &str = "\
+Usage: korones <ROM> [OPTIONS]
+
+Options:
+  --frames <N>            Stop after N frames (default: 600)
+  --until-pc <ADDR>       Stop when PC reaches ADDR
+  --until-mem <ADDR=VAL>  Stop when the byte at ADDR equals VAL
+  --test-rom              Stop when a test ROM reports its result at $6000
+  --init <POLICY>         Power-on RAM: zeros (default), ones, alternating or random:SEED
+  --movie <FILE>          Play an input movie (.fm2, .bk2, .mmo or korones movie) from power-on
+  --sav <FILE>            Keep battery-backed cartridge RAM in FILE
+  --dump-ram <FILE>       Write the 2KB internal RAM to FILE when stopped
+  --trace <FILE>          Write a trace line for every instruction to FILE
+  -h, --help              Print this help
+
+Addresses and values are hexadecimal.";
+
+#[derive(Default)]
+struct Options {
+    rom: String,
+    frames: u64,
+    until_pc: Option<u16>,
+    until_mem: Option<(u16, u8)>,
+    test_rom: bool,
+    init_policy: InitPolicy,
+    movie: Option<String>,
+    sav: Option<String>,
+    dump_ram: Option<String>,
+    trace: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("korones: headless NES runner\n\n{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Options, or `None` when help was asked for
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        frames: 600,
+        ..Options::default()
+    };
+    let mut rom = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--frames" => {
+                options.frames = value()?
+                    .parse()
+                    .map_err(|_| "invalid frame count".to_string())?
+            }
+            "--until-pc" => options.until_pc = Some(parse_hex(value()?)?),
+            "--until-mem" => {
+                let (addr, v) = value()?
+                    .split_once('=')
+                    .ok_or("--until-mem expects ADDR=VAL")?;
+                let v = parse_hex(v)?;
+                let v = u8::try_from(v).map_err(|_| format!("{:X} is not a byte", v))?;
+                options.until_mem = Some((parse_hex(addr)?, v));
+            }
+            "--test-rom" => options.test_rom = true,
+            "--init" => options.init_policy = value()?.parse()?,
+            "--movie" => options.movie = Some(value()?.clone()),
+            "--sav" => options.sav = Some(value()?.clone()),
+            "--dump-ram" => options.dump_ram = Some(value()?.clone()),
+            "--trace" => options.trace = Some(value()?.clone()),
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    options.rom = rom.ok_or("no ROM given")?;
+    Ok(Some(options))
+}
+
+fn parse_hex(s: &str) -> Result<u16, String> {
+    let digits = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex number {}", s))
+}
+
+// Frames to wait before pressing reset when a test ROM asks for it, a bit over 100ms
+const TEST_ROM_RESET_FRAMES: u64 = 7;
+
+#[derive(PartialEq, Eq)]
+enum TestRomReset {
+    Idle,
+    // pressed at the start of this frame
+    At(u64),
+    // pressed, waiting for the ROM to leave $81
+    Done,
+}
+
+enum Stop {
+    Frames,
+    Pc,
+    Memory,
+    TestRom(u8),
+    Desync,
+}
+
+fn run(options: &Options) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let rom = fs::read(&options.rom).map_err(|e| format!("{}: {}", options.rom, e))?;
+    let cartridge = Cartridge::from_ines(&rom)?;
+    let movie = match &options.movie {
+        Some(path) => Some(load_movie(path)?),
+        None => None,
+    };
+    let mut trace = match &options.trace {
+        Some(path) => Some(BufWriter::new(fs::File::create(path)?)),
+        None => None,
+    };
+
+    let mut nes = Nes::new();
+    match &options.sav {
+        Some(path) => {
+            nes.insert_cartridge_with_storage(cartridge, Box::new(FileStorage::new(path)))?
+        }
+        None => nes.insert_cartridge(cartridge),
+    }
+    // movies bring their own
+    nes.set_init_policy(options.init_policy);
+    let mut player = match &movie {
+        Some(movie) => Some(Player::new(movie, &mut nes)?),
+        None => {
+            nes.power_on();
+            None
+        }
+    };
+
+    let start = nes.frame();
+    let mut reset = TestRomReset::Idle;
+    let stop = 'run: loop {
+        if start + options.frames <= nes.frame() {
+            break Stop::Frames;
+        }
+        if matches!(reset, TestRomReset::At(frame) if frame <= nes.frame()) {
+            nes.reset();
+            reset = TestRomReset::Done;
+        }
+        if let Some(player) = player.as_mut() {
+            player.start_frame(&mut nes);
+        }
+
+        let frame = nes.frame();
+        while nes.frame() == frame {
+            if options.until_pc == Some(nes.cpu().pc) {
+                break 'run Stop::Pc;
+            }
+            if let Some((addr, v)) = options.until_mem {
+                if nes.peek(addr) == v {
+                    break 'run Stop::Memory;
+                }
+            }
+            if options.test_rom {
+                match test_rom_status(&nes) {
+                    Some(status @ 0x00..=0x7F) => break 'run Stop::TestRom(status),
+                    Some(0x81) if reset == TestRomReset::Idle => {
+                        reset = TestRomReset::At(nes.frame() + TEST_ROM_RESET_FRAMES);
+                    }
+                    Some(0x81) => {}
+                    _ if reset == TestRomReset::Done => reset = TestRomReset::Idle,
+                    _ => {}
+                }
+            }
+
+            if let Some(w) = trace.as_mut() {
+                writeln!(w, "{}", nes.trace())?;
+            }
+            nes.step();
+        }
+
+        if let Some(player) = player.as_mut() {
+            if let Err(e) = player.end_frame(&nes) {
+                eprintln!("{}", e);
+                break Stop::Desync;
+            }
+        }
+    };
+    if let Some(mut w) = trace {
+        w.flush()?;
+    }
+    nes.flush_save()?;
+    if let Some(path) = &options.dump_ram {
+        fs::write(path, nes.ram())?;
+    }
+
+    let frames = nes.frame() - start;
+    let code = match stop {
+        Stop::Frames if options.until_pc.is_some() || options.until_mem.is_some() => {
+            eprintln!("timed out after {} frames", frames);
+            1
+        }
+        Stop::Frames if options.test_rom => {
+            eprintln!("timed out after {} frames: {}", frames, test_rom_text(&nes));
+            1
+        }
+        Stop::Frames => 0,
+        Stop::Desync => 1,
+        Stop::Pc | Stop::Memory => {
+            eprintln!("stopped at frame {}: {}", frames, nes.trace());
+            0
+        }
+        Stop::TestRom(status) => {
+            println!("{}", test_rom_text(&nes));
+            eprintln!("test ROM result {:02X} at frame {}", status, frames);
+            status
+        }
+    };
+    Ok(ExitCode::from(code))
+}
+
+// https://github.com/christopherpow/nes-test-roms/blob/master/readme.txt
+//
+// $6000 holds the status once $6001-$6003 hold DE B0 61: $80 while running, $81 when the ROM asks
+// for a reset at least 100ms later, and the result code below $80 when done.
+fn test_rom_status(nes: &Nes) -> Option<u8> {
+    let signature = [nes.peek(0x6001), nes.peek(0x6002), nes.peek(0x6003)];
+    (signature == [0xDE, 0xB0, 0x61]).then(|| nes.peek(0x6000))
+}
+
+fn test_rom_text(nes: &Nes) -> String {
+    (0x6004..=0x7FFF)
+        .map(|addr| nes.peek(addr))
+        .take_while(|&b| b != 0)
+        .map(|b| b as char)
+        .collect()
+}
+
+fn load_movie(path: &str) -> Result<Movie, Box<dyn std::error::Error>> {
+    let bytes = fs::read(path)?;
+    let movie = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("bk2") => Movie::from_bk2(&bytes)?,
+        Some("mmo") => Movie::from_mesen(&bytes)?,
+        _ => {
+            let text = String::from_utf8(bytes)?;
+            if text.starts_with("version ") {
+                Movie::from_fm2(&text)?
+            } else {
+                Movie::load(&text)?
+            }
+        }
+    };
+    Ok(movie)
+}
@@ -0,0 +1,366 @@
+//! Input movies
+//!
+//! A `Movie` holds the controller input of every frame from power-on, along with hashes of the
+//! RAM taken while recording, which playback compares to detect desyncs. Movies are saved in a
+//! text format close to FCEUX's `.fm2`, and can be imported from and exported to `.fm2` itself.
+//! BizHawk's `.bk2` and Mesen's `.mmo` movies can be imported too.
+
+mod bk2;
+mod fm2;
+mod mesen;
+mod zip;
+
+use std::fmt;
+use std::hash::Hasher;
+
+use crate::cartridge::Cartridge;
+use crate::fnv::Fnv1a;
+use crate::{Buttons, InitPolicy, Nes};
+
+bitflags! {
+    /// Console commands issued at the start of a frame
+    #[derive(Default)]
+    pub struct Command: u8 {
+        const SOFT_RESET = 1 << 0;
+        const POWER = 1 << 1;
+    }
+}
+
+/// Input of a frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub command: Command,
+    pub buttons: [Buttons; 2],
+}
+
+/// Device plugged into a controller port
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    None,
+    #[default]
+    Gamepad,
+}
+
+/// Hash of the RAM after `frame` frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub frame: usize,
+    pub ram_hash: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// MD5 of PRG and CHR ROM the movie was recorded on
+    pub rom_checksum: Option<[u8; 16]>,
+    pub rom_filename: String,
+    pub rerecords: u32,
+    pub ports: [Port; 2],
+    /// Power-on RAM and registers the movie was recorded with
+    pub init_policy: InitPolicy,
+    pub comments: Vec<String>,
+    pub frames: Vec<Frame>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Parse {
+        line: usize,
+        message: String,
+    },
+    // features of the movie the console doesn't have
+    Unsupported(String),
+    RomMismatch,
+    Desync {
+        frame: usize,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+            Error::RomMismatch => write!(f, "the movie was recorded on another ROM"),
+            Error::Desync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "desync at frame {}: RAM hash {:016X}, expected {:016X}",
+                frame, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+const MAGIC: &str = "korones-movie 1";
+
+impl Movie {
+    /// Imports an FCEUX `.fm2` movie
+    pub fn from_fm2(text: &str) -> Result<Self, Error> {
+        fm2::parse(text)
+    }
+
+    /// Exports to FCEUX's `.fm2`, without checkpoints
+    pub fn to_fm2(&self) -> String {
+        fm2::write(self)
+    }
+
+    /// Imports a BizHawk `.bk2` movie
+    pub fn from_bk2(bytes: &[u8]) -> Result<Self, Error> {
+        bk2::parse(bytes)
+    }
+
+    /// Imports a Mesen `.mmo` movie
+    pub fn from_mesen(bytes: &[u8]) -> Result<Self, Error> {
+        mesen::parse(bytes)
+    }
+
+    /// Reads a movie saved by `save`
+    pub fn load(text: &str) -> Result<Self, Error> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim_end()) != Some(MAGIC) {
+            return Err(parse_error(0, "not a korones movie"));
+        }
+
+        let mut movie = Self::default();
+        for (n, line) in lines {
+            if line.starts_with('|') {
+                movie.frames.push(fm2::parse_input(n, line, movie.ports)?);
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "romChecksum" => movie.rom_checksum = Some(parse_hex(n, value)?),
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "rerecords" => movie.rerecords = parse_number(n, value)?,
+                "port0" | "port1" => {
+                    let port = match value {
+                        "none" => Port::None,
+                        "gamepad" => Port::Gamepad,
+                        _ => return Err(parse_error(n, "invalid port")),
+                    };
+                    movie.ports[(key == "port1") as usize] = port;
+                }
+                "initPolicy" => {
+                    movie.init_policy = value.parse().map_err(|e: String| parse_error(n, &e))?;
+                }
+                "comment" => movie.comments.push(value.to_string()),
+                "checkpoint" => {
+                    let (frame, hash) = value
+                        .split_once(' ')
+                        .ok_or_else(|| parse_error(n, "invalid checkpoint"))?;
+                    movie.checkpoints.push(Checkpoint {
+                        frame: parse_number(n, frame)?,
+                        ram_hash: u64::from_str_radix(hash, 16)
+                            .map_err(|_| parse_error(n, "invalid hash"))?,
+                    });
+                }
+                "" => {}
+                _ => return Err(parse_error(n, &format!("unknown key {}", key))),
+            }
+        }
+        Ok(movie)
+    }
+
+    /// Writes the movie in the crate's own format
+    pub fn save(&self) -> String {
+        let mut out = format!("{}\n", MAGIC);
+        if let Some(checksum) = self.rom_checksum {
+            let hex: String = checksum.iter().map(|b| format!("{:02x}", b)).collect();
+            out += &format!("romChecksum {}\n", hex);
+        }
+        out += &format!("romFilename {}\n", self.rom_filename);
+        out += &format!("rerecords {}\n", self.rerecords);
+        for (n, port) in self.ports.iter().enumerate() {
+            let port = match port {
+                Port::None => "none",
+                Port::Gamepad => "gamepad",
+            };
+            out += &format!("port{} {}\n", n, port);
+        }
+        out += &format!("initPolicy {}\n", self.init_policy);
+        for comment in &self.comments {
+            out += &format!("comment {}\n", comment);
+        }
+        for c in &self.checkpoints {
+            out += &format!("checkpoint {} {:016x}\n", c.frame, c.ram_hash);
+        }
+        for frame in &self.frames {
+            out += &fm2::write_input(frame, self.ports);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Checks that the movie was recorded on `cartridge`, if it knows the ROM
+    pub fn check_rom(&self, cartridge: &Cartridge) -> Result<(), Error> {
+        match self.rom_checksum {
+            Some(checksum) if checksum != cartridge.checksum() => Err(Error::RomMismatch),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Plays a movie back from power-on
+pub struct Player<'a> {
+    movie: &'a Movie,
+    frame: usize,
+    checkpoint: usize,
+}
+
+impl<'a> Player<'a> {
+    /// Checks the inserted cartridge and powers the console on with the movie's init policy
+    pub fn new(movie: &'a Movie, nes: &mut Nes) -> Result<Self, Error> {
+        if let Some(cartridge) = nes.cartridge() {
+            movie.check_rom(cartridge)?;
+        }
+        nes.set_init_policy(movie.init_policy);
+        nes.power_on();
+        Ok(Self {
+            movie,
+            frame: 0,
+            checkpoint: 0,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.movie.frames.len() <= self.frame
+    }
+
+    /// Number of frames played
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Runs the next frame with its input, then compares RAM with a checkpoint of the frame if any
+    pub fn run_frame(&mut self, nes: &mut Nes) -> Result<(), Error> {
+        self.start_frame(nes);
+        nes.run_frame();
+        self.end_frame(nes)
+    }
+
+    /// Applies the input of the next frame, for callers running the frame themselves
+    pub fn start_frame(&mut self, nes: &mut Nes) {
+        let input = self.movie.frames.get(self.frame).copied();
+        apply(nes, input.unwrap_or_default());
+    }
+
+    /// Counts a frame run after `start_frame` and checks RAM
+    pub fn end_frame(&mut self, nes: &Nes) -> Result<(), Error> {
+        self.frame += 1;
+
+        let checkpoints = &self.movie.checkpoints;
+        while let Some(c) = checkpoints.get(self.checkpoint) {
+            if self.frame < c.frame {
+                break;
+            }
+            self.checkpoint += 1;
+            if c.frame == self.frame {
+                let actual = ram_hash(nes);
+                if actual != c.ram_hash {
+                    return Err(Error::Desync {
+                        frame: c.frame,
+                        expected: c.ram_hash,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records a movie from power-on
+pub struct Recorder {
+    movie: Movie,
+    // frames between checkpoints, or 0 for none
+    checkpoint_interval: usize,
+}
+
+impl Recorder {
+    /// Powers the console on and starts recording
+    pub fn new(nes: &mut Nes, checkpoint_interval: usize) -> Self {
+        let movie = Movie {
+            rom_checksum: nes.cartridge().map(|c| c.checksum()),
+            init_policy: nes.init_policy(),
+            ..Movie::default()
+        };
+        nes.power_on();
+        Self {
+            movie,
+            checkpoint_interval,
+        }
+    }
+
+    pub fn run_frame(&mut self, nes: &mut Nes, input: Frame) {
+        apply(nes, input);
+        nes.run_frame();
+        self.movie.frames.push(input);
+
+        let frame = self.movie.frames.len();
+        if self.checkpoint_interval != 0 && frame.is_multiple_of(self.checkpoint_interval) {
+            self.movie.checkpoints.push(Checkpoint {
+                frame,
+                ram_hash: ram_hash(nes),
+            });
+        }
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+fn apply(nes: &mut Nes, input: Frame) {
+    if input.command.contains(Command::POWER) {
+        nes.power_on();
+    } else if input.command.contains(Command::SOFT_RESET) {
+        nes.reset();
+    }
+    for (port, buttons) in input.buttons.iter().enumerate() {
+        nes.set_buttons(port, *buttons);
+    }
+}
+
+fn ram_hash(nes: &Nes) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(nes.ram());
+    hasher.finish()
+}
+
+fn parse_error(line: usize, message: &str) -> Error {
+    Error::Parse {
+        line: line + 1,
+        message: message.to_string(),
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Unsupported(what.to_string())
+}
+
+fn parse_number<T: std::str::FromStr>(line: usize, s: &str) -> Result<T, Error> {
+    s.trim()
+        .parse()
+        .map_err(|_| parse_error(line, &format!("invalid number {}", s)))
+}
+
+fn parse_hex(line: usize, s: &str) -> Result<[u8; 16], Error> {
+    let s = s.trim();
+    let mut checksum = [0; 16];
+    if s.len() != 32 || !s.is_ascii() {
+        return Err(parse_error(line, "invalid checksum"));
+    }
+    for (n, b) in checksum.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[n * 2..n * 2 + 2], 16)
+            .map_err(|_| parse_error(line, "invalid checksum"))?;
+    }
+    Ok(checksum)
+}
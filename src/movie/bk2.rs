@@ -0,0 +1,153 @@
+// BizHawk movie format
+// https://tasvideos.org/Bizhawk/BK2Format
+//
+// A zip archive of Header.txt, Input Log.txt and optionally Comments.txt. The input log starts
+// with a LogKey naming the buttons of each field of the input lines.
+
+use super::zip::Archive;
+use super::{parse_error, parse_number, unsupported, Command, Error, Frame, Movie, Port};
+use crate::Buttons;
+
+const GAMEPAD_BUTTONS: [(&str, Buttons); 8] = [
+    ("Up", Buttons::UP),
+    ("Down", Buttons::DOWN),
+    ("Left", Buttons::LEFT),
+    ("Right", Buttons::RIGHT),
+    ("Start", Buttons::START),
+    ("Select", Buttons::SELECT),
+    ("B", Buttons::B),
+    ("A", Buttons::A),
+];
+
+#[derive(Clone, Copy)]
+enum Key {
+    Command(Command),
+    Button(usize, Buttons),
+}
+
+pub(super) fn parse(bytes: &[u8]) -> Result<Movie, Error> {
+    let archive = Archive::parse(bytes).ok_or_else(|| parse_error(0, "not a zip archive"))?;
+    let text = |name: &str| {
+        archive
+            .read(name)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    };
+
+    let mut movie = Movie::default();
+    let header = text("Header.txt").ok_or_else(|| parse_error(0, "no Header.txt"))?;
+    let mut platform = None;
+    for (n, line) in header.lines().enumerate() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let flag = || value.trim().eq_ignore_ascii_case("true");
+        match key {
+            "Platform" => platform = Some(value.trim().to_string()),
+            "GameName" => movie.rom_filename = value.to_string(),
+            "rerecordCount" => movie.rerecords = parse_number(n, value)?,
+            "Author" => movie.comments.push(format!("author {}", value)),
+            "PAL" if flag() => return Err(unsupported("PAL")),
+            "StartsFromSavestate" if flag() => {
+                return Err(unsupported("starting from a savestate"))
+            }
+            "StartsFromSaveRam" if flag() => return Err(unsupported("starting from SaveRAM")),
+            // MovieVersion, emuVersion, Core, SHA1 (of the ROM file, not PRG and CHR), BoardName...
+            _ => {}
+        }
+    }
+    match platform.as_deref() {
+        Some("NES") => {}
+        Some(p) => return Err(unsupported(&format!("platform {}", p))),
+        None => return Err(parse_error(0, "no Platform in Header.txt")),
+    }
+    if let Some(comments) = text("Comments.txt") {
+        movie.comments.extend(comments.lines().map(str::to_string));
+    }
+
+    let log = text("Input Log.txt").ok_or_else(|| parse_error(0, "no Input Log.txt"))?;
+    let mut keys: Option<Vec<Vec<Key>>> = None;
+    for (n, line) in log.lines().enumerate() {
+        if let Some(log_key) = line.strip_prefix("LogKey:") {
+            let parsed = parse_log_key(log_key)?;
+            movie.ports = [0, 1].map(|port| {
+                let gamepad = parsed
+                    .iter()
+                    .flatten()
+                    .any(|k| matches!(k, Key::Button(p, _) if *p == port));
+                if gamepad {
+                    Port::Gamepad
+                } else {
+                    Port::None
+                }
+            });
+            keys = Some(parsed);
+        } else if line.starts_with('|') {
+            let keys = keys
+                .as_ref()
+                .ok_or_else(|| parse_error(n, "input before LogKey"))?;
+            movie.frames.push(parse_input(n, line, keys)?);
+        }
+        // [Input] and [/Input]
+    }
+    Ok(movie)
+}
+
+// #Reset|Power|#P1 Up|P1 Down|...|P1 A|#P2 Up|...|
+fn parse_log_key(log_key: &str) -> Result<Vec<Vec<Key>>, Error> {
+    log_key
+        .split('#')
+        .filter(|group| !group.is_empty())
+        .map(|group| {
+            group
+                .split('|')
+                .filter(|name| !name.is_empty())
+                .map(parse_key)
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_key(name: &str) -> Result<Key, Error> {
+    match name {
+        "Reset" => return Ok(Key::Command(Command::SOFT_RESET)),
+        "Power" => return Ok(Key::Command(Command::POWER)),
+        _ => {}
+    }
+    let button = |player: &str, button: &str| {
+        let port = match player {
+            "P1" => 0,
+            "P2" => 1,
+            _ => return None,
+        };
+        GAMEPAD_BUTTONS
+            .iter()
+            .find(|(b, _)| *b == button)
+            .map(|(_, b)| Key::Button(port, *b))
+    };
+    name.split_once(' ')
+        .and_then(|(player, b)| button(player, b))
+        // Zapper, Four Score players 3 and 4, FDS disk controls, Famicom expansion devices...
+        .ok_or_else(|| unsupported(&format!("controller configuration with {}", name)))
+}
+
+fn parse_input(n: usize, line: &str, keys: &[Vec<Key>]) -> Result<Frame, Error> {
+    let fields: Vec<&str> = line.trim_end().trim_matches('|').split('|').collect();
+    if fields.len() != keys.len() {
+        return Err(parse_error(n, "input doesn't match LogKey"));
+    }
+
+    let mut frame = Frame::default();
+    for (field, keys) in fields.iter().zip(keys) {
+        if field.len() != keys.len() {
+            return Err(parse_error(n, "input doesn't match LogKey"));
+        }
+        for (c, key) in field.bytes().zip(keys) {
+            if c == b'.' || c == b' ' {
+                continue;
+            }
+            match *key {
+                Key::Command(command) => frame.command |= command,
+                Key::Button(port, button) => frame.buttons[port] |= button,
+            }
+        }
+    }
+    Ok(frame)
+}
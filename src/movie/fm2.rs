@@ -0,0 +1,194 @@
+// FCEUX movie format
+// https://fceux.com/web/help/fm2.html
+
+use super::{parse_error, parse_number, unsupported, Command, Error, Frame, Movie, Port};
+use crate::Buttons;
+
+// Button order of an input log field
+const BUTTONS: [Buttons; 8] = [
+    Buttons::RIGHT,
+    Buttons::LEFT,
+    Buttons::DOWN,
+    Buttons::UP,
+    Buttons::START,
+    Buttons::SELECT,
+    Buttons::B,
+    Buttons::A,
+];
+const MNEMONICS: &[u8; 8] = b"RLDUTSBA";
+
+// FCEUX commands other than soft reset and power: FDS disk insert/select, VS coin insert and so on
+const UNSUPPORTED_COMMANDS: u8 = !(Command::SOFT_RESET.bits() | Command::POWER.bits());
+
+pub(super) fn parse(text: &str) -> Result<Movie, Error> {
+    let mut movie = Movie::default();
+    let mut version = None;
+
+    for (n, line) in text.lines().enumerate() {
+        if line.starts_with('|') {
+            movie.frames.push(parse_input(n, line, movie.ports)?);
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let flag = || value.trim() != "0";
+        match key {
+            "version" => version = Some(parse_number::<u32>(n, value)?),
+            "binary" if flag() => return Err(unsupported("binary input log")),
+            "palFlag" if flag() => return Err(unsupported("PAL")),
+            "fourscore" if flag() => return Err(unsupported("Four Score")),
+            "FDS" if flag() => return Err(unsupported("Famicom Disk System")),
+            "port2" if flag() => return Err(unsupported("Famicom expansion port device")),
+            "port0" | "port1" => {
+                let port = match value.trim() {
+                    "0" => Port::None,
+                    "1" => Port::Gamepad,
+                    device => {
+                        return Err(unsupported(&format!("{} device {}", key, device)));
+                    }
+                };
+                movie.ports[(key == "port1") as usize] = port;
+            }
+            "romFilename" => movie.rom_filename = value.to_string(),
+            "romChecksum" => {
+                let checksum = value
+                    .trim()
+                    .strip_prefix("base64:")
+                    .and_then(decode_base64)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| parse_error(n, "invalid romChecksum"))?;
+                movie.rom_checksum = Some(checksum);
+            }
+            "rerecordCount" => movie.rerecords = parse_number(n, value)?,
+            "comment" => movie.comments.push(value.to_string()),
+            // emuVersion, guid, microphone, NewPPU, subtitle and so on
+            _ => {}
+        }
+    }
+
+    match version {
+        Some(3) => Ok(movie),
+        Some(v) => Err(unsupported(&format!("version {}", v))),
+        None => Err(parse_error(0, "no version")),
+    }
+}
+
+pub(super) fn write(movie: &Movie) -> String {
+    let port = |p: Port| match p {
+        Port::None => 0,
+        Port::Gamepad => 1,
+    };
+
+    let mut out = String::new();
+    out += "version 3\n";
+    out += "emuVersion 22020\n";
+    out += &format!("rerecordCount {}\n", movie.rerecords);
+    out += "palFlag 0\n";
+    out += &format!("romFilename {}\n", movie.rom_filename);
+    if let Some(checksum) = movie.rom_checksum {
+        out += &format!("romChecksum base64:{}\n", encode_base64(&checksum));
+    }
+    out += "guid 00000000-0000-0000-0000-000000000000\n";
+    out += "fourscore 0\n";
+    out += "microphone 0\n";
+    out += &format!("port0 {}\n", port(movie.ports[0]));
+    out += &format!("port1 {}\n", port(movie.ports[1]));
+    out += "port2 0\n";
+    out += "FDS 0\n";
+    out += "NewPPU 0\n";
+    for comment in &movie.comments {
+        out += &format!("comment {}\n", comment);
+    }
+    for frame in &movie.frames {
+        out += &write_input(frame, movie.ports);
+        out.push('\n');
+    }
+    out
+}
+
+// |commands|port0|port1|port2|
+pub(super) fn parse_input(n: usize, line: &str, ports: [Port; 2]) -> Result<Frame, Error> {
+    let fields: Vec<&str> = line.split('|').collect();
+    if fields.len() < 5 {
+        return Err(parse_error(n, "invalid input log"));
+    }
+
+    let command: u8 = parse_number(n, fields[1])?;
+    if command & UNSUPPORTED_COMMANDS != 0 {
+        return Err(unsupported(&format!("command {}", command)));
+    }
+    let mut frame = Frame {
+        command: Command::from_bits_truncate(command),
+        ..Frame::default()
+    };
+
+    for (port, field) in fields[2..4].iter().enumerate() {
+        if ports[port] == Port::None {
+            continue;
+        }
+        if field.len() != 8 {
+            return Err(parse_error(n, "invalid gamepad input"));
+        }
+        for (c, button) in field.bytes().zip(BUTTONS) {
+            if c != b'.' && c != b' ' {
+                frame.buttons[port] |= button;
+            }
+        }
+    }
+    Ok(frame)
+}
+
+pub(super) fn write_input(frame: &Frame, ports: [Port; 2]) -> String {
+    let mut out = format!("|{}|", frame.command.bits());
+    for (port, buttons) in ports.iter().zip(frame.buttons) {
+        if *port == Port::Gamepad {
+            for (c, button) in MNEMONICS.iter().zip(BUTTONS) {
+                out.push(if buttons.contains(button) {
+                    *c as char
+                } else {
+                    '.'
+                });
+            }
+        }
+        out.push('|');
+    }
+    out.push('|');
+    out
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(v >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut out = Vec::new();
+    let (mut v, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let d = BASE64.iter().position(|&b| b == c)? as u32;
+        v = v << 6 | d;
+        bits += 6;
+        if 8 <= bits {
+            bits -= 8;
+            out.push((v >> bits) as u8);
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,120 @@
+// Mesen movie format (.mmo)
+// https://www.mesen.ca/docs/
+//
+// A zip archive of GameSettings.txt, holding the emulator settings the movie was recorded with
+// as "key value" lines, and Input.txt, with a line of input per frame: the system actions (RP for
+// reset and power) then the state of each controller, each field starting with a '|'.
+
+use super::zip::Archive;
+use super::{parse_error, parse_number, unsupported, Command, Error, Frame, Movie, Port};
+use crate::{Buttons, InitPolicy};
+
+const SYSTEM_ACTIONS: [Command; 2] = [Command::SOFT_RESET, Command::POWER];
+// Mnemonics UDLRSsBA
+const BUTTONS: [Buttons; 8] = [
+    Buttons::UP,
+    Buttons::DOWN,
+    Buttons::LEFT,
+    Buttons::RIGHT,
+    Buttons::START,
+    Buttons::SELECT,
+    Buttons::B,
+    Buttons::A,
+];
+
+pub(super) fn parse(bytes: &[u8]) -> Result<Movie, Error> {
+    let archive = Archive::parse(bytes).ok_or_else(|| parse_error(0, "not a zip archive"))?;
+    let text = |name: &str| {
+        archive
+            .read(name)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    };
+    if archive.names().any(|name| name == "SaveState.mst") {
+        return Err(unsupported("starting from a savestate"));
+    }
+
+    let mut movie = Movie::default();
+    let settings = text("GameSettings.txt").ok_or_else(|| parse_error(0, "no GameSettings.txt"))?;
+    for (n, line) in settings.lines().enumerate() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = value.trim();
+        match key {
+            "MovieFormatVersion" => {
+                let version: u32 = parse_number(n, value)?;
+                if version != 1 {
+                    return Err(unsupported(&format!("version {}", version)));
+                }
+            }
+            "GameFile" => movie.rom_filename = value.to_string(),
+            "Region" if value != "NTSC" && value != "Auto" => {
+                return Err(unsupported(&format!("region {}", value)));
+            }
+            "ConsoleType" if value != "Nes" => {
+                return Err(unsupported(&format!("console type {}", value)));
+            }
+            "Controller1" | "Controller2" => {
+                let port = match value {
+                    "None" => Port::None,
+                    "StandardController" => Port::Gamepad,
+                    _ => return Err(unsupported(&format!("{} {}", key, value))),
+                };
+                movie.ports[(key == "Controller2") as usize] = port;
+            }
+            "RamPowerOnState" => {
+                movie.init_policy = match value {
+                    "AllZeros" => InitPolicy::Zeros,
+                    "AllOnes" => InitPolicy::Ones,
+                    // without Mesen's seed the RAM can't be reproduced
+                    _ => return Err(unsupported(&format!("{} {}", key, value))),
+                };
+            }
+            "Controller3" | "Controller4" | "ExpansionDevice" if value != "None" => {
+                return Err(unsupported(&format!("{} {}", key, value)));
+            }
+            // MesenVersion, SHA1 (of the ROM file, not PRG and CHR), emulation settings...
+            _ => {}
+        }
+    }
+
+    let input = text("Input.txt").ok_or_else(|| parse_error(0, "no Input.txt"))?;
+    for (n, line) in input.lines().enumerate() {
+        if line.starts_with('|') {
+            movie.frames.push(parse_input(n, line, movie.ports)?);
+        }
+    }
+    Ok(movie)
+}
+
+// |RP|UDLRSsBA|UDLRSsBA
+fn parse_input(n: usize, line: &str, ports: [Port; 2]) -> Result<Frame, Error> {
+    let mut fields = line.trim_end()[1..].split('|');
+    let mut frame = Frame::default();
+
+    let actions = fields.next().unwrap_or_default();
+    if actions.len() != SYSTEM_ACTIONS.len() {
+        return Err(parse_error(n, "invalid system actions"));
+    }
+    for (c, command) in actions.bytes().zip(SYSTEM_ACTIONS) {
+        if c != b'.' {
+            frame.command |= command;
+        }
+    }
+
+    for (port, kind) in ports.iter().enumerate() {
+        if *kind == Port::None {
+            continue;
+        }
+        let field = fields
+            .next()
+            .ok_or_else(|| parse_error(n, "missing controller input"))?;
+        if field.len() != BUTTONS.len() {
+            return Err(parse_error(n, "invalid controller input"));
+        }
+        for (c, button) in field.bytes().zip(BUTTONS) {
+            if c != b'.' {
+                frame.buttons[port] |= button;
+            }
+        }
+    }
+    Ok(frame)
+}
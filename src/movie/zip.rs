@@ -0,0 +1,265 @@
+// Minimal zip reader for movie archives, with stored and deflated entries
+// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+// https://www.rfc-editor.org/rfc/rfc1951
+
+pub(super) struct Archive<'a> {
+    bytes: &'a [u8],
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    compressed_size: usize,
+    local_header: usize,
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+impl<'a> Archive<'a> {
+    pub(super) fn parse(bytes: &'a [u8]) -> Option<Self> {
+        // End of central directory record, followed by a comment of up to 64KB
+        let eocd = (0..=bytes.len().checked_sub(22)?)
+            .rev()
+            .take(0x10000 + 22)
+            .find(|&at| u32_at(bytes, at) == Some(0x06054B50))?;
+        let count = u16_at(bytes, eocd + 10)? as usize;
+        let mut at = u32_at(bytes, eocd + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(bytes, at)? != 0x02014B50 {
+                return None;
+            }
+            let name_len = u16_at(bytes, at + 28)? as usize;
+            let extra_len = u16_at(bytes, at + 30)? as usize;
+            let comment_len = u16_at(bytes, at + 32)? as usize;
+            let name = bytes.get(at + 46..at + 46 + name_len)?;
+            entries.push(Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(bytes, at + 10)?,
+                compressed_size: u32_at(bytes, at + 20)? as usize,
+                local_header: u32_at(bytes, at + 42)? as usize,
+            });
+            at += 46 + name_len + extra_len + comment_len;
+        }
+        Some(Self { bytes, entries })
+    }
+
+    pub(super) fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// Contents of the entry `name`, or `None` if it's missing or broken
+    pub(super) fn read(&self, name: &str) -> Option<Vec<u8>> {
+        let entry = self.entries.iter().find(|e| e.name == name)?;
+        let at = entry.local_header;
+        if u32_at(self.bytes, at)? != 0x04034B50 {
+            return None;
+        }
+        let start =
+            at + 30 + u16_at(self.bytes, at + 26)? as usize + u16_at(self.bytes, at + 28)? as usize;
+        let data = self.bytes.get(start..start + entry.compressed_size)?;
+        match entry.method {
+            0 => Some(data.to_vec()),
+            8 => inflate(data),
+            _ => None,
+        }
+    }
+}
+
+struct Bits<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        let mut v = 0;
+        for i in 0..n {
+            let byte = *self.bytes.get(self.at)?;
+            v |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.at += 1;
+            }
+        }
+        Some(v)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.at += 1;
+        }
+    }
+}
+
+// Canonical Huffman code as the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order of code length code lengths in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut bits = Bits {
+        bytes: data,
+        at: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let len = u16_at(data, bits.at)? as usize;
+                let start = bits.at + 4;
+                out.extend_from_slice(data.get(start..start + len)?);
+                bits.at = start + len;
+            }
+            1 => {
+                let mut lengths = [0; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return None,
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Option<(Huffman, Huffman)> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &n in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[n] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            18 => (0, 11 + bits.bits(7)?),
+            _ => return None,
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if literal_count + distance_count < lengths.len() {
+        return None;
+    }
+    Some((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<()> {
+    loop {
+        match literals.decode(bits)? as usize {
+            symbol @ 0..=255 => out.push(symbol as u8),
+            256 => return Some(()),
+            symbol => {
+                let n = symbol - 257;
+                let len = *LENGTH_BASE.get(n)? as usize
+                    + bits.bits(*LENGTH_EXTRA.get(n)? as u32)? as usize;
+                let d = distances.decode(bits)? as usize;
+                let distance = *DISTANCE_BASE.get(d)? as usize
+                    + bits.bits(*DISTANCE_EXTRA.get(d)? as u32)? as usize;
+                let from = out.len().checked_sub(distance)?;
+                for i in 0..len {
+                    out.push(out[from + i]);
+                }
+            }
+        }
+    }
+}
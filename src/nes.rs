@@ -1,11 +1,49 @@
-mod cpu;
-use crate::nes::cpu::Emu as cpuEmu;
+mod init;
+mod joypad;
 
-struct Nes {
-    cpu: cpu::Cpu,
+use std::hash::{Hash, Hasher};
+use std::io;
 
+use crate::cartridge::{Battery, Cartridge, Storage};
+use crate::cpu::{Bus, Cpu, Interrupt, Variant};
+use crate::fnv::Fnv1a;
+
+pub use init::InitPolicy;
+pub use joypad::Buttons;
+use joypad::Joypad;
+
+// NTSC frame length until the PPU exists, 29780.5 CPU cycles
+const CPU_CYCLES_PER_TWO_FRAMES: u128 = 59561;
+
+// Random streams of InitPolicy
+const INIT_CPU_RAM: u64 = 0;
+const INIT_REGISTERS: u64 = 1;
+const INIT_CARTRIDGE_RAM: u64 = 2;
+const INIT_BOARD_RAM: u64 = 3;
+
+/// NES console
+///
+/// Emulation is deterministic: the same ROM, init policy and input give the same state on every
+/// machine, which `state_hash` lets callers check.
+#[derive(Clone)]
+pub struct Nes {
+    cpu: Cpu,
+    bus: CpuBus,
+    init_policy: InitPolicy,
+    // where battery-backed memory of the cartridge is saved
+    battery: Option<Battery>,
+    // frames before the last power-on, so `frame` keeps counting across power cycles
+    frame_base: u64,
+}
+
+// Everything connected to the CPU bus
+#[derive(Clone)]
+struct CpuBus {
     cpu_cycle: u128,
-    cpu_wram: [u8; 0x2000],
+    cpu_wram: [u8; 0x800],
+
+    cartridge: Option<Cartridge>,
+    joypads: [Joypad; 2],
 
     // current interrupt status
     //
@@ -13,46 +51,248 @@ struct Nes {
     interrupt: Option<Interrupt>,
 }
 
-/// Kinds of CPU interrupts
-///
-/// It currently supports NMI and IRQ only.
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code, clippy::upper_case_acronyms)]
-pub enum Interrupt {
-    NMI,
-    IRQ,
+impl Default for Nes {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-struct Emu {}
+impl Nes {
+    pub fn new() -> Self {
+        Self {
+            cpu: Cpu::new(Variant::Ricoh2A03),
+            bus: CpuBus {
+                cpu_cycle: 0,
+                cpu_wram: [0; 0x800],
+                cartridge: None,
+                joypads: Default::default(),
+                interrupt: None,
+            },
+            init_policy: InitPolicy::default(),
+            battery: None,
+            frame_base: 0,
+        }
+    }
+
+    /// Sets the contents of RAM, cartridge RAM without a battery, and A, X and Y on power-on
+    pub fn set_init_policy(&mut self, policy: InitPolicy) {
+        self.init_policy = policy;
+    }
+
+    pub fn init_policy(&self) -> InitPolicy {
+        self.init_policy
+    }
+
+    /// Inserts a cartridge whose battery-backed memory isn't kept
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) {
+        self.bus.cartridge = Some(cartridge);
+        self.battery = None;
+    }
 
-impl Emu {
-    fn step(&mut self, nes: &mut Nes) {
-        self.cpu_step(nes);
+    /// Inserts a cartridge, restoring its battery-backed memory from `storage`
+    ///
+    /// The memory goes back to `storage` on `flush_save` and `eject_cartridge`.
+    pub fn insert_cartridge_with_storage(
+        &mut self,
+        mut cartridge: Cartridge,
+        storage: Box<dyn Storage>,
+    ) -> io::Result<()> {
+        let battery = Battery::load(storage, &mut cartridge)?;
+        self.bus.cartridge = Some(cartridge);
+        self.battery = Some(battery);
+        Ok(())
+    }
+
+    /// Saves battery-backed memory to the storage given on insertion
+    ///
+    /// Nothing is written if the memory is unchanged, so hosts can call this often, every second
+    /// or so, to keep saves from being lost.
+    pub fn flush_save(&mut self) -> io::Result<()> {
+        match (&mut self.battery, &self.bus.cartridge) {
+            (Some(battery), Some(cartridge)) => battery.flush(cartridge),
+            _ => Ok(()),
+        }
+    }
+
+    /// Saves battery-backed memory and takes the cartridge out
+    pub fn eject_cartridge(&mut self) -> io::Result<Option<Cartridge>> {
+        self.flush_save()?;
+        self.battery = None;
+        Ok(self.bus.cartridge.take())
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.bus.cartridge.as_ref()
+    }
+
+    pub fn power_on(&mut self) {
+        // nothing from before the power cycle is kept, so the state only depends on the policy
+        self.frame_base = self.frame();
+        self.bus.cpu_cycle = 0;
+        self.bus.interrupt = None;
+        for joypad in &mut self.bus.joypads {
+            joypad.power_on();
+        }
+
+        let policy = self.init_policy;
+        policy.fill(&mut self.bus.cpu_wram, INIT_CPU_RAM);
+        if let Some(cartridge) = self.bus.cartridge.as_mut() {
+            cartridge.power_on(policy, INIT_CARTRIDGE_RAM, INIT_BOARD_RAM);
+        }
+
+        self.cpu.power_on(&mut self.bus);
+        // the CPU only defines P and S at power-on
+        let mut registers = [0; 3];
+        policy.fill(&mut registers, INIT_REGISTERS);
+        [self.cpu.a, self.cpu.x, self.cpu.y] = registers;
+        // https://wiki.nesdev.com/w/index.php/CPU_power_up_state
+        // frame irq disabled
+        self.bus.cpu_write(0x4017, 0x00);
+        // all channels disabled
+        self.bus.cpu_write(0x4015, 0x00);
+
+        for a in 0x4000..=0x400F {
+            self.bus.cpu_write(a, 0x00);
+        }
+        for a in 0x4010..=0x4013 {
+            self.bus.cpu_write(a, 0x00);
+        }
+    }
+
+    /// Presses the reset button
+    pub fn reset(&mut self) {
+        self.cpu.reset(&mut self.bus);
+    }
+
+    pub fn step(&mut self) {
+        self.cpu.step(&mut self.bus);
+    }
+
+    /// Runs until the next frame starts
+    pub fn run_frame(&mut self) {
+        let frame = self.frame();
+        while self.frame() == frame {
+            self.step();
+        }
+    }
+
+    /// Number of frames since the console was created, across power cycles
+    pub fn frame(&self) -> u64 {
+        self.frame_base + (self.bus.cpu_cycle * 2 / CPU_CYCLES_PER_TWO_FRAMES) as u64
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// Number of CPU cycles since the last power-on
+    pub fn cpu_cycles(&self) -> u128 {
+        self.bus.cpu_cycle
+    }
+
+    /// Internal 2KB RAM
+    pub fn ram(&self) -> &[u8] {
+        &self.bus.cpu_wram
+    }
+
+    /// Reads the CPU address space without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.bus.cpu_peek(addr)
+    }
+
+    /// Sets the buttons held on the controller in `port` 0 or 1
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        self.bus.joypads[port].buttons = buttons;
+    }
+
+    /// Hash of the whole emulation state: the CPU, the bus, RAM, controllers and the cartridge
+    ///
+    /// The hash is stable across machines and builds, so runs can be compared anywhere. It covers
+    /// the PPU and the APU once they are emulated.
+    pub fn state_hash(&self) -> u64 {
+        let mut state = Fnv1a::new();
+        self.cpu.hash(&mut state);
+        self.bus.cpu_cycle.hash(&mut state);
+        self.bus.cpu_wram.hash(&mut state);
+        self.bus.joypads.hash(&mut state);
+        self.bus.interrupt.hash(&mut state);
+        if let Some(cartridge) = &self.bus.cartridge {
+            cartridge.hash_state(&mut state);
+        }
+        state.finish()
+    }
+
+    /// Traces the next instruction and the registers in a format close to nestest.log
+    pub fn trace(&self) -> String {
+        format!(
+            "{} CYC:{}",
+            self.cpu.trace(|addr| self.peek(addr)),
+            self.bus.cpu_cycle
+        )
     }
 }
 
-impl cpu::EmuImpl for Emu {}
+impl CpuBus {
+    fn cpu_peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.cpu_wram[(addr & 0x07FF) as usize],
+            0x4016 => self.joypads[0].peek(),
+            0x4017 => self.joypads[1].peek(),
+            0x4020..=0xFFFF => self.cartridge.as_ref().map_or(0, |c| c.cpu_peek(addr)),
+            //TODO
+            _ => 0u8,
+        }
+    }
+}
 
-impl cpu::MemoryMap for Emu {
-    fn cpu_read(&mut self, nes: &mut Nes, addr: u16) -> u8 {
+impl Bus for CpuBus {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
         match addr {
-            0x0000..=0x1FFF => nes.cpu_wram[addr as usize],
+            0x0000..=0x1FFF => self.cpu_wram[(addr & 0x07FF) as usize],
+            0x4016 => self.joypads[0].read(),
+            0x4017 => self.joypads[1].read(),
+            0x4020..=0xFFFF => self.cartridge.as_mut().map_or(0, |c| c.cpu_read(addr)),
             //TODO
             _ => 0u8,
         }
     }
 
-    fn cpu_write(&mut self, nes: &mut Nes, addr: u16, value: u8) {
+    fn cpu_write(&mut self, addr: u16, value: u8) {
         match addr {
-            0x0000..=0x1FFF => nes.cpu_wram[addr as usize] = value,
+            0x0000..=0x1FFF => self.cpu_wram[(addr & 0x07FF) as usize] = value,
+            // the PPU isn't there yet, but some boards listen to its registers
+            0x2000..=0x3FFF => {
+                if let Some(c) = self.cartridge.as_mut() {
+                    c.snoop_ppu_register(addr & 0x2007, value)
+                }
+            }
+            0x4016 => {
+                for joypad in &mut self.joypads {
+                    joypad.write(value);
+                }
+            }
+            0x4020..=0xFFFF => {
+                if let Some(c) = self.cartridge.as_mut() {
+                    c.cpu_write(addr, value)
+                }
+            }
             //TODO
             _ => {}
         }
     }
-}
 
-impl cpu::TickHandler for Emu {
-    fn on_cpu_tick(&mut self, nes: &mut Nes) {
-        nes.cpu_cycle = nes.cpu_cycle.wrapping_add(1);
+    fn on_cpu_tick(&mut self) {
+        self.cpu_cycle = self.cpu_cycle.wrapping_add(1);
+        if let Some(c) = self.cartridge.as_mut() {
+            c.on_cpu_tick();
+        }
+    }
+
+    fn poll_interrupt(&mut self) -> Option<Interrupt> {
+        match self.interrupt {
+            Some(Interrupt::NMI) => self.interrupt.take(),
+            None if self.cartridge.as_ref().is_some_and(|c| c.irq()) => Some(Interrupt::IRQ),
+            irq => irq,
+        }
     }
 }
@@ -0,0 +1,82 @@
+// https://wiki.nesdev.org/w/index.php?title=CPU_power_up_state
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Contents of RAM and registers at power-on
+///
+/// Real consoles power up with semi-random RAM, which a few games read before writing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InitPolicy {
+    #[default]
+    Zeros,
+    /// All $FF
+    Ones,
+    /// Runs of four $00 then four $FF, as FCEUX initializes RAM
+    Alternating,
+    /// Pseudo-random bytes derived from the seed
+    Random(u64),
+}
+
+impl InitPolicy {
+    /// Fills `memory`. `stream` tells memories apart, so they get different random bytes.
+    pub(crate) fn fill(self, memory: &mut [u8], stream: u64) {
+        match self {
+            InitPolicy::Zeros => memory.fill(0x00),
+            InitPolicy::Ones => memory.fill(0xFF),
+            InitPolicy::Alternating => {
+                for (i, b) in memory.iter_mut().enumerate() {
+                    *b = if i & 4 == 0 { 0x00 } else { 0xFF };
+                }
+            }
+            InitPolicy::Random(seed) => {
+                let mut rng = SplitMix64(seed ^ stream.wrapping_mul(0x9E3779B97F4A7C15));
+                for b in memory.iter_mut() {
+                    *b = rng.next() as u8;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for InitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InitPolicy::Zeros => write!(f, "zeros"),
+            InitPolicy::Ones => write!(f, "ones"),
+            InitPolicy::Alternating => write!(f, "alternating"),
+            InitPolicy::Random(seed) => write!(f, "random:{}", seed),
+        }
+    }
+}
+
+/// Parses the `Display` form: zeros, ones, alternating or random:SEED
+impl FromStr for InitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zeros" => Ok(InitPolicy::Zeros),
+            "ones" => Ok(InitPolicy::Ones),
+            "alternating" => Ok(InitPolicy::Alternating),
+            _ => s
+                .strip_prefix("random:")
+                .and_then(|seed| seed.parse().ok())
+                .map(InitPolicy::Random)
+                .ok_or_else(|| format!("invalid init policy {}", s)),
+        }
+    }
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,57 @@
+// https://wiki.nesdev.org/w/index.php?title=Standard_controller
+
+bitflags! {
+    /// Buttons of a standard controller, in the order they are reported
+    #[derive(Default)]
+    pub struct Buttons: u8 {
+        const A = 1 << 0;
+        const B = 1 << 1;
+        const SELECT = 1 << 2;
+        const START = 1 << 3;
+        const UP = 1 << 4;
+        const DOWN = 1 << 5;
+        const LEFT = 1 << 6;
+        const RIGHT = 1 << 7;
+    }
+}
+
+#[derive(Debug, Default, Clone, Hash)]
+pub(super) struct Joypad {
+    pub(super) buttons: Buttons,
+    strobe: bool,
+    shift: u8,
+}
+
+impl Joypad {
+    // Clears the shift register, keeping the buttons held
+    pub(super) fn power_on(&mut self) {
+        self.strobe = false;
+        self.shift = 0;
+    }
+
+    pub(super) fn write(&mut self, value: u8) {
+        self.strobe = value & 1 == 1;
+        if self.strobe {
+            self.shift = self.buttons.bits();
+        }
+    }
+
+    pub(super) fn read(&mut self) -> u8 {
+        let v = self.peek();
+        if !self.strobe {
+            // 1s are shifted in after all buttons are read
+            self.shift = (self.shift >> 1) | 0x80;
+        }
+        v
+    }
+
+    pub(super) fn peek(&self) -> u8 {
+        // Upper bits are open bus, usually the high byte of $4016/$4017
+        let bit = if self.strobe {
+            self.buttons.bits() & 1
+        } else {
+            self.shift & 1
+        };
+        0x40 | bit
+    }
+}
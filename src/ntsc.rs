@@ -0,0 +1,244 @@
+//! NTSC composite video filter
+//!
+//! Re-creates the composite signal the PPU outputs for a frame of 9-bit pixels (6-bit color and
+//! the 3 emphasis bits of $2001 above it) and decodes it like a TV would, with the artifacts that
+//! come with it: dot crawl, color bleeding and fringes on luma edges.
+//!
+//! After blargg's nes_ntsc: http://slack.net/~ant/libs/ntsc.html
+
+use crate::palette::{composite_signal, NtscParams, Rgb};
+
+// The PPU outputs 8 samples per pixel, in 12ths of a color cycle
+const SAMPLES_PER_PIXEL: usize = 8;
+// Each output pixel covers half an input pixel
+const SAMPLES_PER_OUTPUT: usize = 4;
+// Samples around a line, so filters see blanking instead of the line's ends. A multiple of the
+// color cycle keeps the phase of samples unchanged.
+const PADDING: usize = 24;
+
+/// Filter settings
+///
+/// `sharpness`, `artifacts`, `fringing` and `bleed` range from -1.0 (none) to 1.0 (strongest).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtscSetup {
+    pub picture: NtscParams,
+    /// Luma detail kept
+    pub sharpness: f64,
+    /// Chroma leaking into luma, the source of dot crawl
+    pub artifacts: f64,
+    /// Luma edges leaking into chroma, causing color fringes
+    pub fringing: f64,
+    /// Horizontal blur of colors
+    pub bleed: f64,
+    /// Averages two consecutive fields, which hides dot crawl
+    pub merge_fields: bool,
+}
+
+impl NtscSetup {
+    pub const COMPOSITE: Self = Self {
+        picture: NtscParams {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 1.0,
+            gamma: 1.8,
+        },
+        sharpness: 0.0,
+        artifacts: 0.0,
+        fringing: 0.0,
+        bleed: 0.0,
+        merge_fields: false,
+    };
+
+    pub const SVIDEO: Self = Self {
+        sharpness: 0.2,
+        artifacts: -1.0,
+        fringing: -1.0,
+        ..Self::COMPOSITE
+    };
+
+    pub const RGB: Self = Self {
+        sharpness: 0.2,
+        artifacts: -1.0,
+        fringing: -1.0,
+        bleed: -1.0,
+        ..Self::COMPOSITE
+    };
+
+    pub const MONOCHROME: Self = Self {
+        picture: NtscParams {
+            saturation: 0.0,
+            ..Self::COMPOSITE.picture
+        },
+        sharpness: 0.2,
+        artifacts: -1.0,
+        fringing: -1.0,
+        ..Self::COMPOSITE
+    };
+}
+
+impl Default for NtscSetup {
+    fn default() -> Self {
+        Self::COMPOSITE
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NtscFilter {
+    setup: NtscSetup,
+    // Computed once, so filtering itself needs no libm and gives the same frames everywhere
+    carrier: [(f64, f64); 12],
+    gamma_thresholds: [f64; 255],
+}
+
+impl NtscFilter {
+    pub fn new(setup: NtscSetup) -> Self {
+        Self {
+            setup,
+            carrier: setup.picture.carrier(),
+            gamma_thresholds: setup.picture.gamma_thresholds(),
+        }
+    }
+
+    pub fn setup(&self) -> &NtscSetup {
+        &self.setup
+    }
+
+    /// Width of the output for `width` input pixels
+    pub fn output_width(width: usize) -> usize {
+        width * SAMPLES_PER_PIXEL / SAMPLES_PER_OUTPUT
+    }
+
+    /// Filters a frame of `width` pixels per line
+    ///
+    /// `burst_phase` (0 to 2) is the color burst phase of the frame, which the PPU shifts every
+    /// frame, making the artifacts crawl. A `width` of 0 gives an empty frame.
+    pub fn apply(&self, pixels: &[u16], width: usize, burst_phase: usize) -> Vec<Rgb> {
+        if width == 0 {
+            return Vec::new();
+        }
+        let mut out = Vec::with_capacity(Self::output_width(width) * (pixels.len() / width));
+        let mut line = Line::default();
+        for (y, row) in pixels.chunks_exact(width).enumerate() {
+            if self.setup.merge_fields {
+                let a = self.filter_line(&mut line, row, burst_phase, y);
+                let b = self.filter_line(&mut line, row, burst_phase + 1, y);
+                let mix = |a: u8, b: u8| (a as u16 + b as u16).div_ceil(2) as u8;
+                out.extend(a.iter().zip(b).map(|(a, b)| Rgb {
+                    r: mix(a.r, b.r),
+                    g: mix(a.g, b.g),
+                    b: mix(a.b, b.b),
+                }));
+            } else {
+                out.extend(self.filter_line(&mut line, row, burst_phase, y));
+            }
+        }
+        out
+    }
+
+    fn filter_line(&self, line: &mut Line, row: &[u16], burst_phase: usize, y: usize) -> Vec<Rgb> {
+        let setup = &self.setup;
+        let picture = &setup.picture;
+        let gain = |p: f64| (p.clamp(-1.0, 1.0) + 1.0) / 2.0;
+
+        // A line is 341 pixels, so the phase advances by 341 * 8 % 12 = 4 samples every line
+        let phase = (burst_phase + y) * 4 % 12;
+        line.modulate(row, phase);
+        let len = line.luma.len();
+
+        // Luma: the average over a color cycle has no chroma, sharpened by mixing in the original
+        let luma_cycle = boxcar(&line.luma, 12);
+        let sharpness = gain(setup.sharpness);
+        let chroma_leak = boxcar(&line.chroma, 2);
+        let artifacts = gain(setup.artifacts) / 2.0;
+        let luma: Vec<f64> = (0..len)
+            .map(|s| {
+                luma_cycle[s]
+                    + sharpness * (line.luma[s] - luma_cycle[s])
+                    + artifacts * chroma_leak[s]
+            })
+            .collect();
+
+        // Chroma: demodulated and low-passed over a color cycle, then blurred further by bleed
+        let fringing = gain(setup.fringing);
+        let (i, q): (Vec<f64>, Vec<f64>) = (0..len)
+            .map(|s| {
+                let v = line.chroma[s] + fringing * (line.luma[s] - luma_cycle[s]);
+                let (cos, sin) = self.carrier[(phase + s) % 12];
+                (v * cos, v * sin)
+            })
+            .unzip();
+        let bleed = (gain(setup.bleed) * 12.0).round() as usize * 2;
+        let i = boxcar(&boxcar(&i, 12), bleed);
+        let q = boxcar(&boxcar(&q, 12), bleed);
+
+        (PADDING..len - PADDING)
+            .step_by(SAMPLES_PER_OUTPUT)
+            .map(|s| {
+                let s = s + SAMPLES_PER_OUTPUT / 2;
+                let [r, g, b] = picture.linear_rgb(luma[s], i[s], q[s]);
+                Rgb {
+                    r: self.gamma(r),
+                    g: self.gamma(g),
+                    b: self.gamma(b),
+                }
+            })
+            .collect()
+    }
+
+    // 8-bit level of a linear one, like `NtscParams::rgb` but without `powf`
+    fn gamma(&self, v: f64) -> u8 {
+        self.gamma_thresholds.partition_point(|&t| t <= v) as u8
+    }
+}
+
+// Composite signal of a line split into luma, the average of a pixel's signal over a color cycle,
+// and chroma, the rest
+#[derive(Default)]
+struct Line {
+    luma: Vec<f64>,
+    chroma: Vec<f64>,
+}
+
+impl Line {
+    fn modulate(&mut self, row: &[u16], phase: usize) {
+        self.luma.clear();
+        self.chroma.clear();
+        self.luma.resize(PADDING, 0.0);
+        self.chroma.resize(PADDING, 0.0);
+
+        for (x, &pixel) in row.iter().enumerate() {
+            let color = (pixel & 0x3F) as usize;
+            let emphasis = ((pixel >> 6) & 7) as u8;
+            let cycle: [f64; 12] = std::array::from_fn(|p| composite_signal(color, emphasis, p));
+            let average = cycle.iter().sum::<f64>() / 12.0;
+
+            for k in 0..SAMPLES_PER_PIXEL {
+                let p = (phase + x * SAMPLES_PER_PIXEL + k) % 12;
+                self.luma.push(average);
+                self.chroma.push(cycle[p] - average);
+            }
+        }
+        self.luma.resize(self.luma.len() + PADDING, 0.0);
+        self.chroma.resize(self.chroma.len() + PADDING, 0.0);
+    }
+}
+
+// Moving average over `n` samples centered on each sample
+fn boxcar(signal: &[f64], n: usize) -> Vec<f64> {
+    if n < 2 {
+        return signal.to_vec();
+    }
+    let mut sums = Vec::with_capacity(signal.len() + 1);
+    sums.push(0.0);
+    for v in signal {
+        sums.push(sums[sums.len() - 1] + v);
+    }
+    (0..signal.len())
+        .map(|s| {
+            let from = s.saturating_sub(n / 2);
+            let to = (s + n - n / 2).min(signal.len());
+            (sums[to] - sums[from]) / n as f64
+        })
+        .collect()
+}
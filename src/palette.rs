@@ -0,0 +1,326 @@
+//! Palettes mapping PPU colors to RGB
+//!
+//! A palette has 512 entries, one for each of the 64 colors under each combination of the three
+//! emphasis bits of $2001, in the layout of 512-entry `.pal` files.
+
+use std::fmt;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // size of a .pal file which has neither 64 nor 512 entries
+    InvalidSize(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidSize(n) => write!(f, "invalid .pal size {}", n),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parameters of the NTSC palette generator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtscParams {
+    /// Hue rotation in degrees
+    pub hue: f64,
+    pub saturation: f64,
+    pub contrast: f64,
+    pub brightness: f64,
+    /// Gamma of the display the palette is made for
+    pub gamma: f64,
+}
+
+impl Default for NtscParams {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 1.0,
+            gamma: 1.8,
+        }
+    }
+}
+
+// (cos, sin) of the demodulation angle of each 12th of a color cycle, from a table rather than
+// the platform's libm so decoding gives the same colors everywhere
+const CARRIER: [(f64, f64); 12] = {
+    const C: f64 = 0.8660254037844386; // cos 30°
+    [
+        (1.0, 0.0),
+        (C, 0.5),
+        (0.5, C),
+        (0.0, 1.0),
+        (-0.5, C),
+        (-C, 0.5),
+        (-1.0, 0.0),
+        (-C, -0.5),
+        (-0.5, -C),
+        (0.0, -1.0),
+        (0.5, -C),
+        (C, -0.5),
+    ]
+};
+
+impl NtscParams {
+    // (cos, sin) of the demodulation angle at each phase, in 12ths of a color cycle, rotated by
+    // the hue
+    pub(crate) fn carrier(&self) -> [(f64, f64); 12] {
+        let (sin, cos) = self.hue.to_radians().sin_cos();
+        CARRIER.map(|(c, s)| (c * cos - s * sin, s * cos + c * sin))
+    }
+
+    // Adjusts and converts a decoded YIQ color to RGB, before gamma
+    pub(crate) fn linear_rgb(&self, y: f64, i: f64, q: f64) -> [f64; 3] {
+        let y = ((y - 0.5) * self.contrast + 0.5) * self.brightness;
+        let chroma = self.contrast * self.brightness * self.saturation;
+        let (i, q) = (i * chroma, q * chroma);
+
+        // YIQ to RGB, FCC matrix
+        [
+            y + 0.946882 * i + 0.623557 * q,
+            y - 0.274788 * i - 0.635691 * q,
+            y - 1.108545 * i + 1.709007 * q,
+        ]
+    }
+
+    // Adjusts and converts a decoded YIQ color
+    pub(crate) fn rgb(&self, y: f64, i: f64, q: f64) -> Rgb {
+        let gamma = |v: f64| {
+            let v = if v <= 0.0 {
+                0.0
+            } else {
+                v.powf(2.2 / self.gamma)
+            };
+            (v * 255.0).round().clamp(0.0, 255.0) as u8
+        };
+        let [r, g, b] = self.linear_rgb(y, i, q);
+        Rgb {
+            r: gamma(r),
+            g: gamma(g),
+            b: gamma(b),
+        }
+    }
+
+    // Lowest linear level giving each 8-bit level from 1 to 255 after gamma
+    pub(crate) fn gamma_thresholds(&self) -> [f64; 255] {
+        std::array::from_fn(|n| ((n as f64 + 0.5) / 255.0).powf(self.gamma / 2.2))
+    }
+}
+
+/// RGB PPUs of Vs. System and PlayChoice-10 hardware
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RgbPpu {
+    Rp2C03,
+    /// Revisions of the 2C04, each with the colors of the 2C03 in its own order
+    Rp2C04_0001,
+    Rp2C04_0002,
+    Rp2C04_0003,
+    Rp2C04_0004,
+    Rc2C05,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Rgb>,
+}
+
+impl fmt::Debug for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Palette").finish_non_exhaustive()
+    }
+}
+
+/// The generated 2C02 palette with default parameters
+impl Default for Palette {
+    fn default() -> Self {
+        Self::ntsc(&NtscParams::default())
+    }
+}
+
+// https://wiki.nesdev.org/w/index.php?title=NTSC_video
+//
+// Composite signal levels in volts of the low and high halves of the color wave, by luminance
+const LEVELS_LOW: [f64; 4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH: [f64; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f64 = 0.518;
+const WHITE: f64 = 1.962;
+// Signal scale while an emphasized color's phase is out
+const ATTENUATION: f64 = 0.746;
+
+// Composite signal of a 6-bit color under emphasis bits at `phase`, in 12ths of a color cycle,
+// scaled from black (0.0) to white (1.0)
+pub(crate) fn composite_signal(color: usize, emphasis: u8, phase: usize) -> f64 {
+    // whether the color wave of `hue` is high at `phase`
+    let in_phase = |hue: usize| (hue + phase + 8) % 12 < 6;
+
+    let hue = color & 0x0F;
+    // $xE and $xF are black
+    let level = if 0x0D < hue { 1 } else { (color >> 4) & 3 };
+    let mut signal = match hue {
+        0x00 => LEVELS_HIGH[level],
+        0x0D.. => LEVELS_LOW[level],
+        _ if in_phase(hue) => LEVELS_HIGH[level],
+        _ => LEVELS_LOW[level],
+    };
+    // Red, green and blue emphasis attenuate the signal outside of their phase
+    if (0..3).any(|bit| emphasis & (1 << bit) != 0 && in_phase(bit * 4)) {
+        signal *= ATTENUATION;
+    }
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+// https://wiki.nesdev.org/w/index.php?title=PPU_palettes#2C03_and_2C05
+//
+// 3 bits per channel
+#[rustfmt::skip]
+const RGB_PPU: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o000, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
+];
+
+// https://wiki.nesdev.org/w/index.php?title=PPU_palettes#2C04
+//
+// Color of the 2C03 shown for each color of a 2C04
+#[rustfmt::skip]
+const RP2C04: [[u8; 64]; 4] = [
+    // 2C04-0001
+    [
+        0x35, 0x23, 0x16, 0x22, 0x1C, 0x09, 0x1D, 0x15, 0x20, 0x00, 0x27, 0x05, 0x04, 0x28, 0x08, 0x20,
+        0x21, 0x3E, 0x1F, 0x29, 0x3C, 0x32, 0x36, 0x12, 0x3F, 0x2B, 0x2E, 0x1E, 0x3D, 0x2D, 0x24, 0x01,
+        0x0E, 0x31, 0x33, 0x2A, 0x2C, 0x0C, 0x1B, 0x14, 0x2E, 0x07, 0x34, 0x06, 0x13, 0x02, 0x26, 0x2E,
+        0x2E, 0x19, 0x10, 0x0A, 0x39, 0x03, 0x37, 0x17, 0x0F, 0x11, 0x0B, 0x0D, 0x38, 0x25, 0x18, 0x3A,
+    ],
+    // 2C04-0002
+    [
+        0x2E, 0x27, 0x18, 0x39, 0x3A, 0x25, 0x1C, 0x31, 0x16, 0x13, 0x38, 0x34, 0x20, 0x23, 0x3C, 0x0B,
+        0x0F, 0x21, 0x06, 0x3D, 0x1B, 0x29, 0x1E, 0x22, 0x1D, 0x24, 0x0E, 0x2B, 0x32, 0x08, 0x2E, 0x03,
+        0x04, 0x36, 0x26, 0x33, 0x11, 0x1F, 0x10, 0x02, 0x14, 0x3F, 0x00, 0x09, 0x12, 0x2E, 0x28, 0x20,
+        0x3E, 0x0D, 0x2A, 0x17, 0x0C, 0x01, 0x15, 0x19, 0x2E, 0x2C, 0x07, 0x37, 0x35, 0x05, 0x0A, 0x2D,
+    ],
+    // 2C04-0003
+    [
+        0x14, 0x25, 0x3A, 0x10, 0x0B, 0x20, 0x31, 0x09, 0x01, 0x2E, 0x36, 0x08, 0x15, 0x3D, 0x3E, 0x3C,
+        0x22, 0x1C, 0x05, 0x12, 0x19, 0x18, 0x17, 0x1B, 0x00, 0x03, 0x2E, 0x02, 0x16, 0x06, 0x34, 0x35,
+        0x23, 0x0F, 0x0E, 0x37, 0x0D, 0x27, 0x26, 0x20, 0x29, 0x04, 0x21, 0x24, 0x11, 0x2D, 0x2E, 0x1F,
+        0x2C, 0x1E, 0x39, 0x33, 0x07, 0x2A, 0x28, 0x1D, 0x0A, 0x2E, 0x32, 0x38, 0x13, 0x2B, 0x3F, 0x0C,
+    ],
+    // 2C04-0004
+    [
+        0x18, 0x03, 0x1C, 0x28, 0x2E, 0x35, 0x01, 0x17, 0x10, 0x1F, 0x2A, 0x0E, 0x36, 0x37, 0x0B, 0x39,
+        0x25, 0x1E, 0x12, 0x34, 0x2E, 0x1D, 0x06, 0x26, 0x3E, 0x1B, 0x22, 0x19, 0x04, 0x2E, 0x3A, 0x21,
+        0x05, 0x0A, 0x07, 0x02, 0x13, 0x14, 0x00, 0x15, 0x0C, 0x3D, 0x11, 0x0F, 0x0D, 0x38, 0x2D, 0x24,
+        0x33, 0x20, 0x08, 0x16, 0x3F, 0x2B, 0x20, 0x3C, 0x2E, 0x27, 0x23, 0x31, 0x29, 0x32, 0x2C, 0x09,
+    ],
+];
+
+impl Palette {
+    /// Loads a `.pal` file with 64 or 512 entries
+    ///
+    /// Emphasized colors of a 64-entry palette are derived by attenuating the other channels.
+    pub fn from_pal(bytes: &[u8]) -> Result<Self, Error> {
+        let colors: Vec<Rgb> = bytes
+            .chunks_exact(3)
+            .map(|c| Rgb {
+                r: c[0],
+                g: c[1],
+                b: c[2],
+            })
+            .collect();
+        match bytes.len() {
+            192 => Ok(Self::with_emphasis(|color, emphasis| {
+                let Rgb { r, g, b } = colors[color];
+                let mut channels = [r as f64, g as f64, b as f64];
+                for bit in (0..3).filter(|bit| emphasis & (1 << bit) != 0) {
+                    for (i, c) in channels.iter_mut().enumerate() {
+                        if i != bit {
+                            *c *= ATTENUATION;
+                        }
+                    }
+                }
+                Rgb {
+                    r: channels[0].round() as u8,
+                    g: channels[1].round() as u8,
+                    b: channels[2].round() as u8,
+                }
+            })),
+            1536 => Ok(Self { colors }),
+            n => Err(Error::InvalidSize(n)),
+        }
+    }
+
+    /// Generates the 2C02 palette by decoding its composite signal
+    ///
+    /// After Bisqwit's generator: https://bisqwit.iki.fi/jutut/kuvat/programming_examples/nesemu1/nesemu1.cc
+    pub fn ntsc(params: &NtscParams) -> Self {
+        let carrier = params.carrier();
+        Self::with_emphasis(|color, emphasis| {
+            let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+            for (phase, (cos, sin)) in carrier.into_iter().enumerate() {
+                let v = composite_signal(color, emphasis, phase) / 12.0;
+                y += v;
+                i += v * cos;
+                q += v * sin;
+            }
+            params.rgb(y, i, q)
+        })
+    }
+
+    /// Fixed palette of an RGB PPU
+    ///
+    /// Emphasis bits turn the corresponding channel fully on instead of darkening the others.
+    pub fn rgb_ppu(ppu: RgbPpu) -> Self {
+        Self::with_emphasis(|color, emphasis| {
+            // The 2C05 only swaps registers
+            let rgb = match ppu {
+                RgbPpu::Rp2C03 | RgbPpu::Rc2C05 => RGB_PPU[color],
+                RgbPpu::Rp2C04_0001 => RGB_PPU[RP2C04[0][color] as usize],
+                RgbPpu::Rp2C04_0002 => RGB_PPU[RP2C04[1][color] as usize],
+                RgbPpu::Rp2C04_0003 => RGB_PPU[RP2C04[2][color] as usize],
+                RgbPpu::Rp2C04_0004 => RGB_PPU[RP2C04[3][color] as usize],
+            };
+            let channel = |shift: u16, bit: u8| {
+                let v = if emphasis & bit != 0 {
+                    7
+                } else {
+                    (rgb >> shift) & 7
+                };
+                (v * 255 / 7) as u8
+            };
+            Rgb {
+                r: channel(6, 0b001),
+                g: channel(3, 0b010),
+                b: channel(0, 0b100),
+            }
+        })
+    }
+
+    fn with_emphasis(color: impl Fn(usize, u8) -> Rgb) -> Self {
+        let colors = (0..512).map(|n| color(n & 0x3F, (n >> 6) as u8)).collect();
+        Self { colors }
+    }
+
+    /// RGB of a 6-bit PPU color under the emphasis bits 5-7 of $2001
+    pub fn color(&self, color: u8, mask: u8) -> Rgb {
+        let emphasis = (mask >> 5) as usize;
+        self.colors[emphasis << 6 | (color & 0x3F) as usize]
+    }
+
+    /// Writes the palette as a 512-entry `.pal` file
+    pub fn to_pal(&self) -> Vec<u8> {
+        self.colors.iter().flat_map(|c| [c.r, c.g, c.b]).collect()
+    }
+}
@@ -0,0 +1,291 @@
+use korones::cartridge::Cartridge;
+use korones::Nes;
+
+// 256KB of PRG ROM whose 16KB banks start with their number, `code` at $C000 and `table` at $C100
+fn cartridge(mapper: u16, submapper: u8, code: &[u8], table: &[u8]) -> Cartridge {
+    let mut rom = vec![
+        b'N',
+        b'E',
+        b'S',
+        0x1A,
+        16,
+        0,
+        (mapper as u8) << 4 | 0x02,
+        (mapper as u8 & 0xF0) | 0x08,
+        submapper << 4,
+    ];
+    rom.resize(16, 0);
+    for bank in 0..16 {
+        let mut prg = vec![0; 0x4000];
+        prg[0] = bank;
+        if bank == 15 {
+            prg[..code.len()].copy_from_slice(code);
+            prg[0x100..0x100 + table.len()].copy_from_slice(table);
+            prg[0x3FFC..].copy_from_slice(&[0x00, 0xC0, 0x13, 0xC0]);
+        }
+        rom.extend(prg);
+    }
+    Cartridge::from_ines(&rom).unwrap()
+}
+
+fn run_until(nes: &mut Nes, pc: u16) {
+    for _ in 0..100_000 {
+        if nes.cpu().pc == pc {
+            return;
+        }
+        nes.step();
+    }
+    panic!("{}", nes.trace());
+}
+
+#[test]
+fn prg_banks() {
+    #[rustfmt::skip]
+    let code = [
+        0xA9, 0x03,       // $C000 LDA #$03
+        0x8D, 0x08, 0x80, //       STA $8008
+        0x4C, 0x05, 0xC0, // $C005 JMP $C005
+    ];
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(16, 0, &code, &[]));
+    nes.power_on();
+    assert_eq!(nes.peek(0x8000), 0);
+    run_until(&mut nes, 0xC005);
+    assert_eq!(nes.peek(0x8000), 3);
+    assert_eq!(nes.peek(0xC000), 0xA9);
+}
+
+#[rustfmt::skip]
+const IRQ: [u8; 27] = [
+    0xA9, 0x10,       // $C000 LDA #$10
+    0x8D, 0x0B, 0x80, //       STA $800B
+    0xA9, 0x00,       //       LDA #$00
+    0x8D, 0x0C, 0x80, //       STA $800C
+    0xA9, 0x01,       //       LDA #$01
+    0x8D, 0x0A, 0x80, //       STA $800A
+    0x58,             //       CLI
+    0x4C, 0x10, 0xC0, // $C010 JMP $C010
+    0xE6, 0x00,       // $C013 INC $00
+    0xA9, 0x00,       //       LDA #$00
+    0x8D, 0x0A, 0x80, //       STA $800A
+    0x40,             //       RTI
+];
+
+#[test]
+fn irq() {
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(159, 0, &IRQ, &[]));
+    nes.power_on();
+
+    run_until(&mut nes, 0xC00F);
+    let enabled = nes.cpu_cycles();
+    // the step taking the interrupt runs INC $00 too
+    run_until(&mut nes, 0xC015);
+    // $10 cycles to count down, the instruction in progress, 7 cycles of interrupt and INC
+    let cycles = nes.cpu_cycles() - enabled;
+    assert!(
+        (17 + 7 + 5..=17 + 3 + 7 + 5).contains(&cycles),
+        "{}",
+        cycles
+    );
+
+    // acknowledged and disabled by the handler
+    nes.run_frame();
+    assert_eq!(nes.peek(0x0000), 1);
+}
+
+// Writes `table` to $800D one by one, storing what $6000 reads after each at $0200
+#[rustfmt::skip]
+fn i2c(len: usize) -> [u8; 22] {
+    [
+        0xA2, 0x00,             // $C000 LDX #$00
+        0xBD, 0x00, 0xC1,       // $C002 LDA $C100,X
+        0x8D, 0x0D, 0x80,       //       STA $800D
+        0xAD, 0x00, 0x60,       //       LDA $6000
+        0x9D, 0x00, 0x02,       //       STA $0200,X
+        0xE8,                   //       INX
+        0xE0, len as u8,        //       CPX #len
+        0xD0, 0xEF,             //       BNE $C002
+        0x4C, 0x13, 0xC0,       // $C013 JMP $C013
+    ]
+}
+
+// SCL and SDA levels written to $800D, with where SDA is read back
+#[derive(Default)]
+struct Bus {
+    writes: Vec<u8>,
+    reads: Vec<usize>,
+    msb_first: bool,
+}
+
+impl Bus {
+    fn set(&mut self, scl: bool, sda: bool) {
+        self.writes.push(0x80 | (sda as u8) << 6 | (scl as u8) << 5);
+    }
+
+    fn start(&mut self) {
+        self.set(true, true);
+        self.set(true, false);
+        self.set(false, false);
+    }
+
+    fn stop(&mut self) {
+        self.set(false, false);
+        self.set(true, false);
+        self.set(true, true);
+    }
+
+    fn bit(&mut self, bit: bool) {
+        self.set(false, bit);
+        self.set(true, bit);
+        self.set(false, bit);
+    }
+
+    // released SDA, read while SCL is high
+    fn read_bit(&mut self) {
+        self.set(false, true);
+        self.set(true, true);
+        self.reads.push(self.writes.len() - 1);
+        self.set(false, true);
+    }
+
+    fn order(&self) -> Vec<u32> {
+        if self.msb_first {
+            (0..8).rev().collect()
+        } else {
+            (0..8).collect()
+        }
+    }
+
+    // a byte then the acknowledge from the EEPROM
+    fn write_byte(&mut self, byte: u8) {
+        for i in self.order() {
+            self.bit(byte >> i & 1 == 1);
+        }
+        self.read_bit();
+    }
+
+    // a byte from the EEPROM, then no acknowledge
+    fn read_byte(&mut self) {
+        for _ in 0..8 {
+            self.read_bit();
+        }
+        self.bit(true);
+    }
+
+    fn run(&self, nes: &mut Nes) -> Vec<bool> {
+        run_until(nes, 0xC013);
+        self.reads
+            .iter()
+            .map(|&i| nes.peek(0x0200 + i as u16) & 0x10 != 0)
+            .collect()
+    }
+
+    fn cartridge(&self, mapper: u16) -> Cartridge {
+        assert!(self.writes.len() < 0x100);
+        cartridge(mapper, 0, &i2c(self.writes.len()), &self.writes)
+    }
+}
+
+fn byte(bits: &[bool], msb_first: bool) -> u8 {
+    bits.iter().enumerate().fold(0, |b, (i, &bit)| {
+        let i = if msb_first { 7 - i } else { i };
+        b | (bit as u8) << i
+    })
+}
+
+#[test]
+fn eeprom_24c02() {
+    // writes $12 $34 at $40
+    let mut bus = Bus {
+        msb_first: true,
+        ..Bus::default()
+    };
+    bus.start();
+    bus.write_byte(0xA0);
+    bus.write_byte(0x40);
+    bus.write_byte(0x12);
+    bus.write_byte(0x34);
+    bus.stop();
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge(bus.cartridge(16));
+    nes.power_on();
+    // every byte acknowledged
+    assert_eq!(bus.run(&mut nes), [false; 4]);
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data.len(), 256);
+    assert_eq!(data[0x40..0x42], [0x12, 0x34]);
+
+    // random read of $41
+    let mut bus = Bus {
+        msb_first: true,
+        ..Bus::default()
+    };
+    bus.start();
+    bus.write_byte(0xA0);
+    bus.write_byte(0x41);
+    bus.start();
+    bus.write_byte(0xA1);
+    bus.read_byte();
+    bus.stop();
+
+    let mut cartridge = bus.cartridge(16);
+    cartridge.load_save_data(&data);
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge);
+    nes.power_on();
+    let sda = bus.run(&mut nes);
+    assert_eq!(sda[..3], [false; 3]);
+    assert_eq!(byte(&sda[3..], true), 0x34);
+}
+
+#[test]
+fn eeprom_x24c01() {
+    // writes $5A at $05, then reads it back
+    let mut bus = Bus::default();
+    bus.start();
+    bus.write_byte(0x05);
+    bus.write_byte(0x5A);
+    bus.stop();
+    bus.start();
+    bus.write_byte(0x80 | 0x05);
+    bus.read_byte();
+    bus.stop();
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge(bus.cartridge(159));
+    nes.power_on();
+    let sda = bus.run(&mut nes);
+    assert_eq!(sda[..3], [false; 3]);
+    assert_eq!(byte(&sda[3..], false), 0x5A);
+
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data.len(), 128);
+    assert_eq!(data[5], 0x5A);
+}
+
+#[test]
+fn prg_ram_153() {
+    #[rustfmt::skip]
+    let code = [
+        0xA9, 0x77,       // $C000 LDA #$77
+        0x8D, 0x00, 0x60, //       STA $6000
+        0xA9, 0x20,       //       LDA #$20
+        0x8D, 0x0D, 0x80, //       STA $800D
+        0xA9, 0x55,       //       LDA #$55
+        0x8D, 0x01, 0x60, //       STA $6001
+        0x4C, 0x0F, 0xC0, // $C00F JMP $C00F
+    ];
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(153, 0, &code, &[]));
+    nes.power_on();
+    run_until(&mut nes, 0xC00F);
+
+    // writes need PRG RAM enabled
+    assert_eq!(nes.peek(0x6000), 0x00);
+    assert_eq!(nes.peek(0x6001), 0x55);
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data.len(), 0x2000);
+    assert_eq!(data[..2], [0x00, 0x55]);
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use korones::cartridge::{Cartridge, FileStorage, Storage};
+use korones::Nes;
+
+// NROM-128 counting frames of work at $6000
+#[rustfmt::skip]
+const PROGRAM: [u8; 9] = [
+    0xEE, 0x00, 0x60, // $C000 INC $6000
+    0xEE, 0x01, 0x60, // $C003 INC $6001
+    0x4C, 0x03, 0xC0, // $C006 JMP $C003
+];
+
+fn cartridge(battery: bool) -> Cartridge {
+    let flags6 = if battery { 0x02 } else { 0x00 };
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 0x01, 0x01, flags6, 0x00];
+    rom.resize(16, 0);
+    let mut prg = vec![0; 0x4000];
+    prg[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    prg[0x3FFC] = 0x00;
+    prg[0x3FFD] = 0xC0;
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+    Cartridge::from_ines(&rom).unwrap()
+}
+
+// Storage in memory, counting saves
+#[derive(Clone, Default)]
+struct Memory {
+    data: Rc<RefCell<Option<Vec<u8>>>>,
+    saves: Rc<RefCell<usize>>,
+}
+
+impl Storage for Memory {
+    fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.data.borrow().clone())
+    }
+
+    fn save(&mut self, data: &[u8]) -> io::Result<()> {
+        *self.data.borrow_mut() = Some(data.to_vec());
+        *self.saves.borrow_mut() += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn save_and_restore() {
+    let storage = Memory::default();
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge_with_storage(cartridge(true), Box::new(storage.clone()))
+        .unwrap();
+    nes.power_on();
+    nes.run_frame();
+    assert_eq!(nes.peek(0x6000), 1);
+
+    // nothing to write while the RAM is unchanged
+    nes.flush_save().unwrap();
+    nes.flush_save().unwrap();
+    assert_eq!(*storage.saves.borrow(), 1);
+    let ejected = nes.eject_cartridge().unwrap();
+    assert!(ejected.is_some());
+    assert!(nes.cartridge().is_none());
+    assert_eq!(*storage.saves.borrow(), 1);
+
+    let saved = storage.data.borrow().clone().unwrap();
+    assert_eq!(saved.len(), 0x2000);
+    assert_eq!(saved[0], 1);
+
+    // the next session goes on from the save
+    let mut nes = Nes::new();
+    nes.insert_cartridge_with_storage(cartridge(true), Box::new(storage.clone()))
+        .unwrap();
+    nes.power_on();
+    assert_eq!(nes.peek(0x6001), saved[1]);
+    nes.step();
+    assert_eq!(nes.peek(0x6000), 2);
+}
+
+#[test]
+fn no_battery() {
+    let storage = Memory::default();
+    *storage.data.borrow_mut() = Some(vec![0x55; 0x2000]);
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge_with_storage(cartridge(false), Box::new(storage.clone()))
+        .unwrap();
+    nes.power_on();
+    assert_eq!(nes.peek(0x6001), 0);
+    nes.run_frame();
+    nes.eject_cartridge().unwrap();
+    assert_eq!(*storage.saves.borrow(), 0);
+    assert!(cartridge(false).save_data().is_none());
+}
+
+#[test]
+fn sav_file() {
+    let dir = std::env::temp_dir().join(format!("korones-battery-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("game.sav");
+    // a short save fills the start of PRG RAM
+    std::fs::write(&path, [0x10, 0x20]).unwrap();
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge_with_storage(cartridge(true), Box::new(FileStorage::new(&path)))
+        .unwrap();
+    nes.power_on();
+    nes.step();
+    assert_eq!(nes.peek(0x6000), 0x11);
+    assert_eq!(nes.peek(0x6001), 0x20);
+    nes.flush_save().unwrap();
+
+    let saved = std::fs::read(&path).unwrap();
+    assert_eq!(saved.len(), 0x2000);
+    assert_eq!(saved[..2], [0x11, 0x20]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
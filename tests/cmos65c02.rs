@@ -0,0 +1,33 @@
+// WAI and STP of the 65C02, which keep the bus busy while the CPU idles
+
+mod common;
+
+use common::flat_memory::FlatMemory;
+use korones::cpu::{Cpu, Variant};
+
+// Runs `opcode` at $0400, then `steps` more steps with no interrupt to resume on
+fn idle(opcode: u8, steps: usize) -> FlatMemory {
+    let mut mem = FlatMemory::recording(vec![0; 0x10000]);
+    mem.ram[0x0400] = opcode;
+    let mut cpu = Cpu::new(Variant::Cmos65C02);
+    cpu.pc = 0x0400;
+    for _ in 0..=steps {
+        cpu.step(&mut mem);
+    }
+    assert_eq!(cpu.pc, 0x0401);
+    mem
+}
+
+#[test]
+fn wai_and_stp_access_the_bus_every_cycle() {
+    for opcode in [0xCB, 0xDB] {
+        let mem = idle(opcode, 10);
+        let cycles = mem.cycles.unwrap();
+        assert_eq!(cycles.len(), mem.ticks, "{:02X}", opcode);
+        // three cycles for the instruction, then one read of the next opcode per step
+        assert_eq!(mem.ticks, 13, "{:02X}", opcode);
+        assert!(cycles[1..]
+            .iter()
+            .all(|(addr, _, kind)| *addr == 0x0401 && kind == "read"));
+    }
+}
@@ -0,0 +1,52 @@
+// Flat 64K memory bus for the CPU suites and benchmarks
+//
+// Benchmarks include this file on its own with `#[path]`.
+#![allow(dead_code)]
+
+use korones::cpu::Bus;
+
+pub struct FlatMemory {
+    pub ram: Vec<u8>,
+    // (address, value, "read" or "write") of every access, when recording
+    pub cycles: Option<Vec<(u16, u8, String)>>,
+    pub ticks: usize,
+}
+
+impl FlatMemory {
+    pub fn new(ram: Vec<u8>) -> Self {
+        Self {
+            ram,
+            cycles: None,
+            ticks: 0,
+        }
+    }
+
+    // Memory that records every bus access
+    pub fn recording(ram: Vec<u8>) -> Self {
+        Self {
+            cycles: Some(Vec::new()),
+            ..Self::new(ram)
+        }
+    }
+}
+
+impl Bus for FlatMemory {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let v = self.ram[addr as usize];
+        if let Some(cycles) = &mut self.cycles {
+            cycles.push((addr, v, "read".to_string()));
+        }
+        v
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+        if let Some(cycles) = &mut self.cycles {
+            cycles.push((addr, value, "write".to_string()));
+        }
+    }
+
+    fn on_cpu_tick(&mut self) {
+        self.ticks += 1;
+    }
+}
@@ -0,0 +1,82 @@
+// Fixtures shared by the suites: the flat memory of the CPU suites, and the ROMs and programs of
+// the mapper suites
+//
+// Not every suite uses every helper.
+#![allow(dead_code)]
+
+pub mod flat_memory;
+
+use korones::cartridge::Cartridge;
+use korones::Nes;
+
+// NES 2.0 header for 128KB of PRG ROM and 64KB of CHR ROM, for suites to add flags to
+pub fn header(mapper: u16, submapper: u8) -> [u8; 16] {
+    let mut header = [0; 16];
+    header[..6].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 8, 8]);
+    header[6] = (mapper as u8) << 4;
+    header[7] = (mapper as u8 & 0xF0) | 0x08;
+    header[8] = submapper << 4 | (mapper >> 8) as u8;
+    header
+}
+
+// PRG ROM whose 8KB banks start with their number, with `code` at $E000 and `handler` at $E100
+// for IRQs, and CHR ROM filled with the number of its 1KB banks
+pub fn cartridge(header: &[u8; 16], code: &[u8], handler: &[u8]) -> Cartridge {
+    let mut rom = header.to_vec();
+    for bank in 0..16 {
+        let mut prg = vec![0; 0x2000];
+        prg[0] = bank;
+        if bank == 15 {
+            prg[..code.len()].copy_from_slice(code);
+            prg[0x100..0x100 + handler.len()].copy_from_slice(handler);
+            prg[0x1FFC..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE1]);
+        }
+        rom.extend(prg);
+    }
+    for bank in 0..64 {
+        rom.extend([bank; 0x400]);
+    }
+    Cartridge::from_ines(&rom).unwrap()
+}
+
+// LDA/STA for each write, then `tail`, ending in a loop at the address returned
+pub fn program(writes: &[(u16, u8)], tail: &[u8]) -> (Vec<u8>, u16) {
+    let mut code = vec![];
+    for &(addr, value) in writes {
+        code.extend([0xA9, value, 0x8D, addr as u8, (addr >> 8) as u8]);
+    }
+    code.extend(tail);
+    let end = 0xE000 + code.len() as u16;
+    code.extend([0x4C, end as u8, (end >> 8) as u8]);
+    (code, end)
+}
+
+pub fn run_until(nes: &mut Nes, pc: u16) {
+    for _ in 0..1000 {
+        if nes.cpu().pc == pc {
+            return;
+        }
+        nes.step();
+    }
+    panic!("{}", nes.trace());
+}
+
+// Powers on with the program of `writes` and `tail`, and runs it up to its final loop
+pub fn run(header: &[u8; 16], writes: &[(u16, u8)], tail: &[u8], handler: &[u8]) -> Nes {
+    let (code, end) = program(writes, tail);
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(header, &code, handler));
+    nes.power_on();
+    run_until(&mut nes, end);
+    nes
+}
+
+// Audio output after each of `steps` instructions
+pub fn record(nes: &mut Nes, steps: usize) -> Vec<f32> {
+    (0..steps)
+        .map(|_| {
+            nes.step();
+            nes.cartridge().unwrap().audio_output()
+        })
+        .collect()
+}
@@ -0,0 +1,223 @@
+// Decimal mode test after Bruce Clark's "Decimal Mode" tutorial
+// http://www.6502.org/tutorials/decimal_mode.html#B
+//
+// Like his test program, this runs ADC and SBC for every pair of operands and carry in decimal mode,
+// and compares the accumulator and flags to the results predicted by the program's ADD, SUB1 and
+// SUB2 routines.
+
+mod common;
+
+use common::flat_memory::FlatMemory;
+use korones::cpu::{Cpu, Status, Variant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Flags {
+    n: bool,
+    v: bool,
+    z: bool,
+    c: bool,
+}
+
+impl Flags {
+    fn of(r: u8) -> Self {
+        Self {
+            n: r & 0x80 != 0,
+            v: false,
+            z: r == 0,
+            c: false,
+        }
+    }
+}
+
+// Binary ADC
+fn adc(a: u8, b: u8, c: bool) -> (u8, Flags) {
+    let sum = a as u16 + b as u16 + c as u16;
+    let r = sum as u8;
+    let flags = Flags {
+        v: (a ^ r) & (b ^ r) & 0x80 != 0,
+        c: sum > 0xFF,
+        ..Flags::of(r)
+    };
+    (r, flags)
+}
+
+// Binary SBC
+fn sbc(a: u8, b: u8, c: bool) -> (u8, Flags) {
+    adc(a, !b, c)
+}
+
+fn cmp(a: u8, b: u8) -> bool {
+    b <= a
+}
+
+// Predicted accumulator, carry, and flags of the `ADC N2H,X` step which hold N and V
+fn add(n1: u8, n2: u8, c: bool) -> (u8, bool, Flags) {
+    let n2h = [n2 & 0xF0, (n2 & 0xF0) | 0x0F];
+
+    let (mut a, _) = adc(n1 & 0x0F, n2 & 0x0F, c);
+    let mut x = 0;
+    let mut c = cmp(a, 0x0A);
+    if c {
+        x = 1;
+        a = adc(a, 5, c).0 & 0x0F;
+        c = true;
+    }
+    a |= n1 & 0xF0;
+    let (mut a, vf) = adc(a, n2h[x], c);
+    let mut c = vf.c;
+    if c || cmp(a, 0xA0) {
+        a = adc(a, 0x5F, true).0;
+        c = true;
+    }
+    (a, c, vf)
+}
+
+// Predicted accumulator of SBC on NMOS 6502
+fn sub1(n1: u8, n2: u8, c: bool) -> u8 {
+    let n2h = [n2 & 0xF0, (n2 & 0xF0) | 0x0F];
+
+    let (mut a, f) = sbc(n1 & 0x0F, n2 & 0x0F, c);
+    let mut x = 0;
+    let mut c = f.c;
+    if !c {
+        x = 1;
+        a = sbc(a, 5, c).0 & 0x0F;
+        c = false;
+    }
+    a |= n1 & 0xF0;
+    let (mut a, f) = sbc(a, n2h[x], c);
+    if !f.c {
+        a = sbc(a, 0x5F, false).0;
+    }
+    a
+}
+
+// Predicted accumulator of SBC on 65C02
+fn sub2(n1: u8, n2: u8, c: bool) -> u8 {
+    let n2h = [n2 & 0xF0, (n2 & 0xF0) | 0x0F];
+
+    let (mut a, f) = sbc(n1 & 0x0F, n2 & 0x0F, c);
+    let mut x = 0;
+    let mut c = f.c;
+    if !c {
+        x = 1;
+        a &= 0x0F;
+        c = false;
+    }
+    a |= n1 & 0xF0;
+    let (mut a, f) = sbc(a, n2h[x], c);
+    if !f.c {
+        a = sbc(a, 0x5F, false).0;
+    }
+    if x != 0 {
+        a = sbc(a, 6, true).0;
+    }
+    a
+}
+
+fn predict_adc(variant: Variant, n1: u8, n2: u8, c: bool) -> (u8, Flags) {
+    let (ha, hf) = adc(n1, n2, c);
+    if variant == Variant::Ricoh2A03 {
+        return (ha, hf);
+    }
+    let (ar, cf, vf) = add(n1, n2, c);
+    let flags = match variant {
+        Variant::Cmos65C02 => Flags {
+            v: vf.v,
+            c: cf,
+            ..Flags::of(ar)
+        },
+        _ => Flags {
+            n: vf.n,
+            v: vf.v,
+            z: hf.z,
+            c: cf,
+        },
+    };
+    (ar, flags)
+}
+
+fn predict_sbc(variant: Variant, n1: u8, n2: u8, c: bool) -> (u8, Flags) {
+    let (ha, hf) = sbc(n1, n2, c);
+    match variant {
+        Variant::Ricoh2A03 => (ha, hf),
+        Variant::Nmos6502 => (sub1(n1, n2, c), hf),
+        Variant::Cmos65C02 => {
+            let ar = sub2(n1, n2, c);
+            let flags = Flags {
+                v: hf.v,
+                c: hf.c,
+                ..Flags::of(ar)
+            };
+            (ar, flags)
+        }
+    }
+}
+
+fn execute(
+    cpu: &mut Cpu,
+    mem: &mut FlatMemory,
+    opcode: u8,
+    n1: u8,
+    n2: u8,
+    c: bool,
+) -> (u8, Flags) {
+    mem.ram[0x0200] = opcode;
+    mem.ram[0x0201] = n2;
+    cpu.pc = 0x0200;
+    cpu.a = n1;
+    cpu.p = Status::D | Status::R;
+    cpu.p.set(Status::C, c);
+
+    cpu.step(mem);
+
+    let flags = Flags {
+        n: cpu.p.contains(Status::N),
+        v: cpu.p.contains(Status::V),
+        z: cpu.p.contains(Status::Z),
+        c: cpu.p.contains(Status::C),
+    };
+    (cpu.a, flags)
+}
+
+fn run(variant: Variant) {
+    let mut cpu = Cpu::new(variant);
+    let mut mem = FlatMemory::new(vec![0; 0x10000]);
+
+    for n1 in 0..=0xFFu8 {
+        for n2 in 0..=0xFFu8 {
+            for c in [false, true] {
+                let actual = execute(&mut cpu, &mut mem, 0x69, n1, n2, c);
+                let expected = predict_adc(variant, n1, n2, c);
+                assert_eq!(
+                    actual, expected,
+                    "ADC: A={:02X} M={:02X} C={}",
+                    n1, n2, c as u8
+                );
+
+                let actual = execute(&mut cpu, &mut mem, 0xE9, n1, n2, c);
+                let expected = predict_sbc(variant, n1, n2, c);
+                assert_eq!(
+                    actual, expected,
+                    "SBC: A={:02X} M={:02X} C={}",
+                    n1, n2, c as u8
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn ricoh2a03_ignores_decimal_flag() {
+    run(Variant::Ricoh2A03);
+}
+
+#[test]
+fn nmos6502() {
+    run(Variant::Nmos6502);
+}
+
+#[test]
+fn cmos65c02() {
+    run(Variant::Cmos65C02);
+}
@@ -0,0 +1,144 @@
+use korones::cartridge::Cartridge;
+use korones::{Buttons, InitPolicy, Nes};
+
+// NROM-128 mixing the zero page with the stack page forever and counting A button reads at $0300
+#[rustfmt::skip]
+const PROGRAM: [u8; 36] = [
+    0xA2, 0x00,       // $C000 LDX #$00
+    0xB5, 0x00,       // $C002 LDA $00,X
+    0x7D, 0x00, 0x01, //       ADC $0100,X
+    0x95, 0x00,       //       STA $00,X
+    0xE8,             //       INX
+    0xD0, 0xF6,       //       BNE $C002
+    0xA9, 0x01,       //       LDA #$01
+    0x8D, 0x16, 0x40, //       STA $4016
+    0xA9, 0x00,       //       LDA #$00
+    0x8D, 0x16, 0x40, //       STA $4016
+    0xAD, 0x16, 0x40, //       LDA $4016
+    0x29, 0x01,       //       AND #$01
+    0x6D, 0x00, 0x03, //       ADC $0300
+    0x8D, 0x00, 0x03, //       STA $0300
+    0x4C, 0x00, 0xC0, //       JMP $C000
+];
+
+fn nes(policy: InitPolicy) -> Nes {
+    let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    let mut prg = vec![0; 0x4000];
+    prg[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    prg[0x3FFC] = 0x00;
+    prg[0x3FFD] = 0xC0;
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+
+    let mut nes = Nes::new();
+    nes.insert_cartridge(Cartridge::from_ines(&rom).unwrap());
+    nes.set_init_policy(policy);
+    nes.power_on();
+    nes
+}
+
+#[test]
+fn lockstep() {
+    let mut a = nes(InitPolicy::Random(42));
+    let mut b = nes(InitPolicy::Random(42));
+    assert_eq!(a.state_hash(), b.state_hash());
+
+    let mut hashes = vec![a.state_hash()];
+    for frame in 0..120 {
+        let buttons = if frame % 7 < 3 {
+            Buttons::A
+        } else {
+            Buttons::empty()
+        };
+        for nes in [&mut a, &mut b] {
+            nes.set_buttons(0, buttons);
+            nes.run_frame();
+        }
+        assert_eq!(a.state_hash(), b.state_hash(), "frame {}", frame);
+        assert_eq!(a.ram(), b.ram());
+        hashes.push(a.state_hash());
+    }
+    hashes.dedup();
+    assert_eq!(hashes.len(), 121);
+}
+
+#[test]
+fn input_changes_state() {
+    let mut a = nes(InitPolicy::Random(42));
+    let mut b = nes(InitPolicy::Random(42));
+    a.set_buttons(0, Buttons::A);
+    a.run_frame();
+    b.run_frame();
+    assert_ne!(a.state_hash(), b.state_hash());
+}
+
+#[test]
+fn init_policy() {
+    let zeroed = nes(InitPolicy::Zeros);
+    assert!(zeroed.ram().iter().all(|&b| b == 0));
+    assert_eq!((zeroed.cpu().a, zeroed.cpu().x, zeroed.cpu().y), (0, 0, 0));
+
+    let a = nes(InitPolicy::Random(1));
+    assert!(a.ram().iter().any(|&b| b != 0));
+    assert_eq!(a.ram(), nes(InitPolicy::Random(1)).ram());
+    assert_ne!(a.ram(), nes(InitPolicy::Random(2)).ram());
+    assert_ne!(a.state_hash(), nes(InitPolicy::Random(2)).state_hash());
+
+    let ones = nes(InitPolicy::Ones);
+    assert!(ones.ram().iter().all(|&b| b == 0xFF));
+    assert_eq!(
+        (ones.cpu().a, ones.cpu().x, ones.cpu().y),
+        (0xFF, 0xFF, 0xFF)
+    );
+    assert_eq!(
+        nes(InitPolicy::Alternating).ram()[..12],
+        [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]
+    );
+
+    // cartridge RAM follows the policy too
+    assert_eq!(ones.peek(0x6000), 0xFF);
+    assert_eq!(nes(InitPolicy::Zeros).peek(0x6000), 0x00);
+
+    // power cycling brings the same RAM back
+    let mut b = nes(InitPolicy::Random(1));
+    b.run_frame();
+    assert_ne!(a.ram(), b.ram());
+    b.power_on();
+    assert_eq!(a.ram(), b.ram());
+}
+
+#[test]
+fn power_cycle_forgets_history() {
+    let a = nes(InitPolicy::Random(1));
+    let mut b = nes(InitPolicy::Random(1));
+    // after a read of the controller, with A held
+    b.set_buttons(0, Buttons::A);
+    for _ in 0..30 {
+        b.run_frame();
+    }
+    while b.cpu().pc != 0xC019 {
+        b.step();
+    }
+    b.set_buttons(0, Buttons::empty());
+    assert_ne!(a.state_hash(), b.state_hash());
+
+    b.power_on();
+    assert_eq!(a.state_hash(), b.state_hash());
+    assert_eq!(b.cpu_cycles(), a.cpu_cycles());
+    // frames keep counting
+    assert_eq!(b.frame(), 30);
+}
+
+#[test]
+fn parse_init_policy() {
+    for policy in [
+        InitPolicy::Zeros,
+        InitPolicy::Ones,
+        InitPolicy::Alternating,
+        InitPolicy::Random(12345),
+    ] {
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+    }
+    assert!("random".parse::<InitPolicy>().is_err());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{header, record};
+use korones::Nes;
+
+// Sets each command's parameter, then runs `tail`, ending in a loop
+fn run(commands: &[(u8, u8)], tail: &[u8], handler: &[u8]) -> Nes {
+    let writes: Vec<_> = commands
+        .iter()
+        .flat_map(|&(command, value)| [(0x8000, command), (0xA000, value)])
+        .collect();
+    common::run(&header(69, 0), &writes, tail, handler)
+}
+
+// Writes to the 5B registers through $C000 and $E000
+#[rustfmt::skip]
+fn audio(registers: &[(u8, u8)]) -> Vec<u8> {
+    let mut code = vec![];
+    for &(register, value) in registers {
+        code.extend([
+            0xA9, register, 0x8D, 0x00, 0xC0, // LDA #register, STA $C000
+            0xA9, value, 0x8D, 0x00, 0xE0,    // LDA #value, STA $E000
+        ]);
+    }
+    code
+}
+
+#[test]
+fn banks() {
+    let nes = run(
+        &[(0x9, 3), (0xA, 4), (0xB, 5), (0x8, 2), (0x1, 7)],
+        &[],
+        &[],
+    );
+    assert_eq!(nes.peek(0x6000), 2);
+    assert_eq!(nes.peek(0x8000), 3);
+    assert_eq!(nes.peek(0xA000), 4);
+    assert_eq!(nes.peek(0xC000), 5);
+    assert_eq!(nes.peek(0xE000), 0xA9);
+    let mut cartridge = nes.cartridge().unwrap().clone();
+    assert_eq!(cartridge.ppu_read(0x0400), 7);
+}
+
+#[test]
+fn prg_ram() {
+    // STA $6000
+    let nes = run(&[(0x8, 0xC0)], &[0x8D, 0x00, 0x60], &[]);
+    assert_eq!(nes.peek(0x6000), 0xC0);
+
+    // ROM bank 1, then RAM selected but disabled
+    let nes = run(&[(0x8, 0x01)], &[], &[]);
+    assert_eq!(nes.peek(0x6000), 1);
+    let nes = run(&[(0x8, 0x41)], &[], &[]);
+    assert_eq!(nes.peek(0x6000), 0);
+}
+
+#[test]
+fn irq() {
+    #[rustfmt::skip]
+    let handler = [
+        0xE6, 0x00,       // INC $00
+        0xA9, 0x0D,       // LDA #$0D
+        0x8D, 0x00, 0x80, // STA $8000
+        0xA9, 0x00,       // LDA #$00
+        0x8D, 0x00, 0xA0, // STA $A000
+        0x40,             // RTI
+    ];
+    let mut nes = run(&[(0xE, 0x10), (0xF, 0x00), (0xD, 0x81)], &[0x58], &handler);
+    nes.run_frame();
+    // acknowledged and disabled by the handler
+    assert_eq!(nes.peek(0x0000), 1);
+}
+
+#[test]
+fn square() {
+    // channel A at full volume, the others silent, no noise
+    let code = audio(&[(0x00, 0x01), (0x07, 0x3E), (0x08, 0x0F)]);
+    let mut nes = run(&[], &code, &[]);
+    let levels = record(&mut nes, 100);
+    assert!(levels.contains(&0.0));
+    assert!(levels.contains(&(1.0 / 3.0)));
+}
+
+#[test]
+fn envelope() {
+    // channel A on the envelope, tones and noise off, decaying then silent
+    let code = audio(&[(0x07, 0x3F), (0x08, 0x10), (0x0B, 0x01), (0x0D, 0x00)]);
+    let mut nes = run(&[], &code, &[]);
+    let levels = record(&mut nes, 200);
+    assert!(levels.windows(2).all(|w| w[1] <= w[0]), "{:?}", levels);
+    assert_eq!(levels.last(), Some(&0.0));
+
+    // attacking then held at the top
+    let code = audio(&[(0x07, 0x3F), (0x08, 0x10), (0x0B, 0x01), (0x0D, 0x0D)]);
+    let mut nes = run(&[], &code, &[]);
+    let levels = record(&mut nes, 200);
+    assert!(levels.windows(2).all(|w| w[1] >= w[0]), "{:?}", levels);
+    assert_eq!(levels.last(), Some(&(1.0 / 3.0)));
+}
+
+#[test]
+fn power_cycle() {
+    let mut nes = run(&[(0x9, 3), (0x8, 2), (0x1, 7)], &[], &[]);
+    nes.power_on();
+    // banks back to 0
+    assert_eq!(nes.peek(0x6000), 0);
+    assert_eq!(nes.peek(0x8000), 0);
+    let mut cartridge = nes.cartridge().unwrap().clone();
+    assert_eq!(cartridge.ppu_read(0x0400), 0);
+}
@@ -0,0 +1,67 @@
+// Klaus Dormann's functional tests
+// https://github.com/Klaus2m5/6502_65C02_functional_tests
+//
+// The test binaries aren't bundled with this crate, so these tests are ignored by default. Clone the
+// repository into `tests/` or point `FUNCTIONAL_TESTS_DIR` to its `bin_files` directory, then run
+//
+//     cargo test --release --test functional_test -- --ignored
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::flat_memory::FlatMemory;
+use korones::cpu::{Cpu, Variant};
+
+// Current test number in the `test_case` variable of the data segment
+const TEST_CASE: usize = 0x0200;
+
+// Runs a test image until it traps in a jump or branch to itself
+//
+// Every failed check ends in such a trap, so trapping anywhere but `success` is a failure.
+fn run(variant: Variant, file: &str, success: u16) {
+    let path = std::env::var_os("FUNCTIONAL_TESTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/6502_65C02_functional_tests/bin_files")
+        })
+        .join(file);
+    let ram = fs::read(&path)
+        .unwrap_or_else(|e| panic!("{}: {}, see tests/functional_test.rs", path.display(), e));
+    assert_eq!(ram.len(), 0x10000, "{} is not a 64K image", path.display());
+
+    let mut mem = FlatMemory::new(ram);
+    let mut cpu = Cpu::new(variant);
+    cpu.pc = 0x0400;
+
+    loop {
+        let pc = cpu.pc;
+        cpu.step(&mut mem);
+        if cpu.pc == pc {
+            break;
+        }
+    }
+    assert_eq!(
+        cpu.pc, success,
+        "trapped at {:04X} in test case {:02X}",
+        cpu.pc, mem.ram[TEST_CASE]
+    );
+}
+
+#[test]
+#[ignore = "needs the functional test binaries"]
+fn nmos6502() {
+    run(Variant::Nmos6502, "6502_functional_test.bin", 0x3469);
+}
+
+#[test]
+#[ignore = "needs the functional test binaries"]
+fn cmos65c02_extended_opcodes() {
+    run(
+        Variant::Cmos65C02,
+        "65C02_extended_opcodes_test.bin",
+        0x24F1,
+    );
+}
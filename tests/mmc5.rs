@@ -0,0 +1,211 @@
+mod common;
+
+use common::{header, record};
+use korones::cartridge::Cartridge;
+use korones::Nes;
+
+// Powers on and runs LDA/STA for each write, with 64KB of battery-backed PRG RAM
+fn run(writes: &[(u16, u8)]) -> Nes {
+    run_with_ram(writes, 0xA0)
+}
+
+// `prg_ram` is byte 10 of the NES 2.0 header
+fn run_with_ram(writes: &[(u16, u8)], prg_ram: u8) -> Nes {
+    let mut header = header(5, 0);
+    header[6] |= 0x02;
+    header[10] = prg_ram;
+    common::run(&header, writes, &[], &[])
+}
+
+// Fetches of a background tile: nametable, attribute, then both pattern planes
+fn fetch_tile(cartridge: &mut Cartridge, column: u16) -> (Option<u8>, Option<u8>, u8) {
+    let nametable = cartridge.nametable_read(0x2000 + column);
+    let attribute = cartridge.nametable_read(0x23C0 + column / 4);
+    let tile = nametable.unwrap_or(0) as u16;
+    let pattern = cartridge.ppu_read(tile * 16);
+    cartridge.ppu_read(tile * 16 + 8);
+    (nametable, attribute, pattern)
+}
+
+// A scanline after its first fetch: the rest of the tiles, sprites, the first two tiles of the
+// next line, then the two dummy nametable fetches the next line starts with
+fn finish_line(cartridge: &mut Cartridge) {
+    for column in 3..34 {
+        fetch_tile(cartridge, column % 32);
+    }
+    for _ in 0..8 {
+        cartridge.nametable_read(0x2000);
+        cartridge.nametable_read(0x2000);
+        cartridge.ppu_read(0x1000);
+        cartridge.ppu_read(0x1008);
+    }
+    fetch_tile(cartridge, 0);
+    fetch_tile(cartridge, 1);
+    cartridge.nametable_read(0x2002);
+    cartridge.nametable_read(0x2002);
+}
+
+fn eject(nes: &mut Nes) -> Cartridge {
+    nes.eject_cartridge().unwrap().unwrap()
+}
+
+#[test]
+fn prg_banks() {
+    let nes = run(&[(0x5100, 3), (0x5114, 0x81), (0x5115, 0x02)]);
+    assert_eq!(nes.peek(0x8000), 1);
+    // RAM
+    assert_eq!(nes.peek(0xA000), 0);
+    assert_eq!(nes.peek(0xE000), 0xA9);
+
+    // 16KB banks
+    let nes = run(&[(0x5100, 1), (0x5115, 0x85)]);
+    assert_eq!(nes.peek(0x8000), 4);
+    assert_eq!(nes.peek(0xA000), 5);
+    assert_eq!(nes.peek(0xC000), 14);
+
+    // one 32KB bank
+    let nes = run(&[(0x5100, 0)]);
+    assert_eq!(nes.peek(0x8000), 12);
+}
+
+#[test]
+fn prg_ram_protect() {
+    let nes = run(&[
+        (0x6000, 0x11),
+        (0x5102, 2),
+        (0x5103, 1),
+        (0x6001, 0x22),
+        (0x5113, 1),
+        (0x6000, 0x33),
+    ]);
+    assert_eq!(nes.peek(0x6000), 0x33);
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data.len(), 0x10000);
+    assert_eq!(data[..2], [0x00, 0x22]);
+    assert_eq!(data[0x2000], 0x33);
+}
+
+#[test]
+fn prg_ram_size() {
+    // two 8KB chips, the second one picked by bit 2 of the bank
+    let writes = [(0x5102, 2), (0x5103, 1), (0x5113, 4), (0x6000, 0x44)];
+    let nes = run_with_ram(&writes, 0x80);
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data.len(), 0x4000);
+    assert_eq!(data[0x2000], 0x44);
+
+    // none
+    let nes = run_with_ram(&writes, 0x00);
+    assert_eq!(nes.peek(0x6000), 0);
+}
+
+#[test]
+fn multiplier() {
+    let nes = run(&[(0x5205, 12), (0x5206, 34)]);
+    assert_eq!(nes.peek(0x5205), 0x98);
+    assert_eq!(nes.peek(0x5206), 0x01);
+}
+
+#[test]
+fn exram_and_fill() {
+    let mut nes = run(&[
+        (0x5104, 2),
+        (0x5C00, 0x12),
+        (0x5105, 0xE4),
+        (0x5106, 0x42),
+        (0x5107, 2),
+    ]);
+    assert_eq!(nes.peek(0x5C00), 0x12);
+
+    let mut cartridge = eject(&mut nes);
+    // CIRAM, CIRAM, ExRAM, fill
+    assert_eq!(cartridge.nametable_read(0x2000), None);
+    assert_eq!(cartridge.nametable_read(0x2400), None);
+    // ExRAM used as RAM isn't a nametable
+    assert_eq!(cartridge.nametable_read(0x2800), Some(0));
+    assert_eq!(cartridge.nametable_read(0x2C00), Some(0x42));
+    assert_eq!(cartridge.nametable_read(0x2FC0), Some(0xAA));
+}
+
+#[test]
+fn scanline_irq() {
+    let mut nes = run(&[(0x2001, 0x18), (0x5203, 2), (0x5204, 0x80)]);
+    let mut cartridge = eject(&mut nes);
+    cartridge.nametable_read(0x2002);
+    cartridge.nametable_read(0x2002);
+    for _ in 0..2 {
+        fetch_tile(&mut cartridge, 2);
+        finish_line(&mut cartridge);
+    }
+    nes.insert_cartridge(cartridge);
+    assert_eq!(nes.peek(0x5204), 0x40);
+
+    let mut cartridge = eject(&mut nes);
+    fetch_tile(&mut cartridge, 2);
+    nes.insert_cartridge(cartridge);
+    assert_eq!(nes.peek(0x5204), 0xC0);
+
+    // out of frame once the PPU stops reading
+    nes.step();
+    assert_eq!(nes.peek(0x5204) & 0x40, 0);
+}
+
+#[test]
+fn extended_attributes() {
+    // palette 2 and 4KB CHR bank 5 for the tile at column 2
+    let mut nes = run(&[(0x5104, 2), (0x5C02, 0x85), (0x5104, 1)]);
+    let mut cartridge = eject(&mut nes);
+    cartridge.nametable_read(0x2002);
+    cartridge.nametable_read(0x2002);
+    assert_eq!(fetch_tile(&mut cartridge, 2), (None, Some(0xAA), 20));
+    // the next tile uses its own byte
+    assert_eq!(fetch_tile(&mut cartridge, 3), (None, Some(0x00), 0));
+}
+
+#[test]
+fn split_screen() {
+    // tiles 0-3 from the split, scrolled by a line, with the CHR of 4KB bank 1
+    let mut nes = run(&[
+        (0x5104, 2),
+        (0x5C02, 0x33),
+        (0x5FC0, 0x08),
+        (0x5104, 0),
+        (0x5200, 0x84),
+        (0x5201, 1),
+        (0x5202, 1),
+    ]);
+    let mut cartridge = eject(&mut nes);
+    cartridge.nametable_read(0x2002);
+    cartridge.nametable_read(0x2002);
+    assert_eq!(fetch_tile(&mut cartridge, 2), (Some(0x33), Some(0xAA), 4));
+    fetch_tile(&mut cartridge, 3);
+    // right of the split
+    assert_eq!(fetch_tile(&mut cartridge, 4), (None, None, 0));
+}
+
+#[test]
+fn pulse() {
+    // pulse 1 at a constant full volume, halted
+    let mut nes = run(&[(0x5015, 1), (0x5000, 0xBF), (0x5002, 0x08), (0x5003, 0x08)]);
+    assert_eq!(nes.peek(0x5015), 0x01);
+    let levels = record(&mut nes, 100);
+    assert!(levels.contains(&0.0));
+    assert!(levels.contains(&(15.0 / 45.0)));
+
+    // a length of 2, run out after two clocks at 240Hz
+    let mut nes = run(&[(0x5015, 1), (0x5000, 0x1F), (0x5003, 0x18)]);
+    assert_eq!(nes.peek(0x5015), 0x01);
+    nes.run_frame();
+    assert_eq!(nes.peek(0x5015), 0x00);
+    assert_eq!(nes.cartridge().unwrap().audio_output(), 0.0);
+
+    // disabled
+    let nes = run(&[(0x5000, 0xBF), (0x5003, 0x08)]);
+    assert_eq!(nes.peek(0x5015), 0x00);
+}
+
+#[test]
+fn pcm() {
+    let nes = run(&[(0x5011, 0xFF)]);
+    assert_eq!(nes.cartridge().unwrap().audio_output(), 15.0 / 45.0);
+}
@@ -0,0 +1,477 @@
+use korones::cartridge::Cartridge;
+use korones::movie::{Command, Error, Frame, Movie, Player, Port, Recorder};
+use korones::{Buttons, InitPolicy, Nes};
+
+// NROM-128 counting resets at $0300 and A button reads at $0301
+#[rustfmt::skip]
+const PROGRAM: [u8; 28] = [
+    0xEE, 0x00, 0x03, // $C000 INC $0300
+    0xA9, 0x01,       // $C003 LDA #$01
+    0x8D, 0x16, 0x40, //       STA $4016
+    0xA9, 0x00,       //       LDA #$00
+    0x8D, 0x16, 0x40, //       STA $4016
+    0xAD, 0x16, 0x40, //       LDA $4016
+    0x29, 0x01,       //       AND #$01
+    0x18,             //       CLC
+    0x6D, 0x01, 0x03, //       ADC $0301
+    0x8D, 0x01, 0x03, //       STA $0301
+    0x4C, 0x03, 0xC0, //       JMP $C003
+];
+
+// MD5 of PRG and CHR ROM
+const CHECKSUM: &str = "7324136621a921891dc65e71c421baf3";
+
+fn cartridge() -> Cartridge {
+    let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    let mut prg = vec![0; 0x4000];
+    prg[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    prg[0x3FFC] = 0x00;
+    prg[0x3FFD] = 0xC0;
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+    Cartridge::from_ines(&rom).unwrap()
+}
+
+fn nes() -> Nes {
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge());
+    nes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn record(frames: usize) -> Movie {
+    let mut nes = nes();
+    let mut recorder = Recorder::new(&mut nes, 10);
+    for n in 0..frames {
+        let mut input = Frame::default();
+        if n % 3 == 0 {
+            input.buttons[0] = Buttons::A | Buttons::RIGHT;
+        }
+        if n == 20 {
+            input.command = Command::SOFT_RESET;
+        }
+        recorder.run_frame(&mut nes, input);
+    }
+    recorder.finish()
+}
+
+fn play(movie: &Movie) -> Result<Nes, Error> {
+    let mut nes = nes();
+    let mut player = Player::new(movie, &mut nes)?;
+    while !player.finished() {
+        player.run_frame(&mut nes)?;
+    }
+    Ok(nes)
+}
+
+#[test]
+fn rom_checksum() {
+    assert_eq!(hex(&cartridge().checksum()), CHECKSUM);
+}
+
+#[test]
+fn record_and_play_back() {
+    let movie = record(60);
+    assert_eq!(movie.frames.len(), 60);
+    assert_eq!(movie.checkpoints.len(), 6);
+    assert_eq!(hex(&movie.rom_checksum.unwrap()), CHECKSUM);
+
+    let nes = play(&movie).unwrap();
+    // Powered on and reset once
+    assert_eq!(nes.peek(0x0300), 2);
+    assert_ne!(nes.peek(0x0301), 0);
+
+    let saved = Movie::load(&movie.save()).unwrap();
+    assert_eq!(saved, movie);
+}
+
+#[test]
+fn init_policy() {
+    let mut nes = nes();
+    nes.set_init_policy(InitPolicy::Random(7));
+    let mut recorder = Recorder::new(&mut nes, 1);
+    for _ in 0..10 {
+        recorder.run_frame(&mut nes, Frame::default());
+    }
+    let movie = recorder.finish();
+    assert_eq!(movie.init_policy, InitPolicy::Random(7));
+
+    // played back on a console set up otherwise
+    let saved = Movie::load(&movie.save()).unwrap();
+    assert_eq!(saved.init_policy, InitPolicy::Random(7));
+    assert_eq!(play(&saved).unwrap().ram(), nes.ram());
+
+    let mut other = saved.clone();
+    other.init_policy = InitPolicy::Zeros;
+    assert!(matches!(play(&other), Err(Error::Desync { .. })));
+}
+
+#[test]
+fn desync() {
+    let mut movie = record(30);
+    movie.frames[16].buttons[0] = Buttons::A;
+
+    match play(&movie) {
+        Err(Error::Desync { frame, .. }) => assert_eq!(frame, 20),
+        r => panic!("no desync: {:?}", r.err()),
+    }
+}
+
+#[test]
+fn rom_mismatch() {
+    let mut movie = record(1);
+    movie.rom_checksum = Some([0; 16]);
+    assert!(matches!(play(&movie), Err(Error::RomMismatch)));
+}
+
+const FM2: &str = "\
+version 3
+emuVersion 22020
+rerecordCount 42
+palFlag 0
+romFilename test
+romChecksum base64:cyQTZiGpIYkdxl5xxCG68w==
+guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B
+fourscore 0
+microphone 0
+port0 1
+port1 0
+port2 0
+FDS 0
+NewPPU 0
+comment author someone
+|0|........|||
+|0|R......A|||
+|1|...UT...|||
+|2|.L...SB.|||
+";
+
+#[test]
+fn import_fm2() {
+    let movie = Movie::from_fm2(FM2).unwrap();
+
+    assert_eq!(hex(&movie.rom_checksum.unwrap()), CHECKSUM);
+    assert_eq!(movie.rom_filename, "test");
+    assert_eq!(movie.rerecords, 42);
+    assert_eq!(movie.ports, [Port::Gamepad, Port::None]);
+    assert_eq!(movie.comments, ["author someone"]);
+
+    let frame = |command, buttons| Frame {
+        command,
+        buttons: [buttons, Buttons::empty()],
+    };
+    assert_eq!(
+        movie.frames,
+        [
+            frame(Command::empty(), Buttons::empty()),
+            frame(Command::empty(), Buttons::RIGHT | Buttons::A),
+            frame(Command::SOFT_RESET, Buttons::UP | Buttons::START),
+            frame(Command::POWER, Buttons::LEFT | Buttons::SELECT | Buttons::B),
+        ]
+    );
+
+    play(&movie).unwrap();
+}
+
+#[test]
+fn export_fm2() {
+    let movie = Movie::from_fm2(FM2).unwrap();
+    let fm2 = movie.to_fm2();
+
+    assert!(fm2.contains("romChecksum base64:cyQTZiGpIYkdxl5xxCG68w==\n"));
+    assert!(fm2.ends_with("|2|.L...SB.|||\n"));
+    assert_eq!(Movie::from_fm2(&fm2).unwrap(), movie);
+}
+
+#[test]
+fn unsupported_fm2() {
+    let unsupported = |from: &str, to: &str| {
+        let fm2 = FM2.replace(from, to);
+        matches!(Movie::from_fm2(&fm2), Err(Error::Unsupported(_)))
+    };
+    assert!(unsupported("fourscore 0", "fourscore 1"));
+    assert!(unsupported("port1 0", "port1 2"));
+    assert!(unsupported("palFlag 0", "palFlag 1"));
+    assert!(unsupported("|2|", "|4|"));
+}
+
+// Zip archive of (name, compression method, data) entries, without CRCs
+fn zip(entries: &[(&str, u16, &[u8])]) -> Vec<u8> {
+    let (mut out, mut directory) = (Vec::new(), Vec::new());
+    for (name, method, data) in entries {
+        let offset = out.len() as u32;
+        let fields = |signature: u32, version: &[u8]| {
+            let mut header = signature.to_le_bytes().to_vec();
+            header.extend(version);
+            header.extend([0, 0]);
+            header.extend(method.to_le_bytes());
+            header.extend([0; 8]);
+            header.extend((data.len() as u32).to_le_bytes());
+            header.extend([0; 4]);
+            header.extend((name.len() as u16).to_le_bytes());
+            header.extend([0; 2]);
+            header
+        };
+        out.extend(fields(0x04034B50, &[20, 0]));
+        out.extend(name.as_bytes());
+        out.extend(*data);
+
+        directory.extend(fields(0x02014B50, &[20, 0, 20, 0]));
+        directory.extend([0; 10]);
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+    let start = out.len() as u32;
+    out.extend(&directory);
+    out.extend(0x06054B50u32.to_le_bytes());
+    out.extend([0; 4]);
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((directory.len() as u32).to_le_bytes());
+    out.extend(start.to_le_bytes());
+    out.extend([0; 2]);
+    out
+}
+
+const BK2_HEADER: &str = "\
+MovieVersion BizHawk v2.0.0
+Author someone
+emuVersion Version 2.9.1
+rerecordCount 42
+Platform NES
+GameName test
+SHA1 0000000000000000000000000000000000000000
+BoardName NROM
+Core NesHawk
+";
+
+const BK2_INPUT: &str = "\
+[Input]
+LogKey:#Reset|Power|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|#P2 Up|P2 Down|P2 Left|P2 Right|P2 Start|P2 Select|P2 B|P2 A|
+|..|........|........|
+|..|.......A|...R....|
+|r.|U...S...|........|
+|.P|..L..sB.|.D......|
+[/Input]
+";
+
+fn bk2(header: &str, input: &str) -> Vec<u8> {
+    zip(&[
+        ("Header.txt", 0, header.as_bytes()),
+        ("Input Log.txt", 0, input.as_bytes()),
+        ("Comments.txt", 0, b"hello"),
+    ])
+}
+
+#[test]
+fn import_bk2() {
+    let movie = Movie::from_bk2(&bk2(BK2_HEADER, BK2_INPUT)).unwrap();
+
+    assert_eq!(movie.rom_checksum, None);
+    assert_eq!(movie.rom_filename, "test");
+    assert_eq!(movie.rerecords, 42);
+    assert_eq!(movie.ports, [Port::Gamepad, Port::Gamepad]);
+    assert_eq!(movie.comments, ["author someone", "hello"]);
+
+    let frame = |command, p1, p2| Frame {
+        command,
+        buttons: [p1, p2],
+    };
+    assert_eq!(
+        movie.frames,
+        [
+            frame(Command::empty(), Buttons::empty(), Buttons::empty()),
+            frame(Command::empty(), Buttons::A, Buttons::RIGHT),
+            frame(
+                Command::SOFT_RESET,
+                Buttons::UP | Buttons::START,
+                Buttons::empty()
+            ),
+            frame(
+                Command::POWER,
+                Buttons::LEFT | Buttons::SELECT | Buttons::B,
+                Buttons::DOWN
+            ),
+        ]
+    );
+
+    play(&movie).unwrap();
+}
+
+// Raw deflate stream of an input log with a dynamic Huffman block
+const BK2_DEFLATED_INPUT: [u8; 268] = [
+    0x65, 0x93, 0xC1, 0x6A, 0xC3, 0x30, 0x10, 0x44, 0xEF, 0xF9, 0x8A, 0x42, 0xEE, 0x53, 0x7A, 0xED,
+    0x4D, 0x42, 0x97, 0x52, 0x1D, 0x82, 0x4D, 0x4F, 0x21, 0x87, 0x50, 0xD4, 0xB4, 0x50, 0xE2, 0x10,
+    0xBB, 0x84, 0x82, 0x3E, 0xBE, 0x92, 0x13, 0xAC, 0x7D, 0xA9, 0x30, 0xD2, 0x0E, 0xBB, 0xEC, 0xEC,
+    0xCC, 0xE2, 0xED, 0xCB, 0xF1, 0xF4, 0x33, 0xED, 0x56, 0x71, 0x38, 0xBC, 0xA6, 0xDF, 0xE7, 0x75,
+    0x97, 0xC6, 0x34, 0xE5, 0xCD, 0x70, 0x49, 0xE7, 0xBC, 0xDE, 0x3C, 0x3D, 0xBC, 0x9D, 0x72, 0xB9,
+    0xC3, 0x70, 0x39, 0xD6, 0x37, 0xA6, 0x8F, 0xA9, 0xBE, 0xDD, 0xD7, 0xE1, 0x73, 0x0E, 0xFA, 0x69,
+    0x7F, 0xBE, 0x06, 0xE9, 0x3B, 0xBD, 0xCF, 0x91, 0xAF, 0x97, 0xCB, 0xAB, 0x2C, 0x95, 0xEF, 0x7A,
+    0x6E, 0x28, 0xA8, 0x03, 0x92, 0xC6, 0x05, 0xA9, 0xE6, 0xFC, 0x82, 0x62, 0xD7, 0x8F, 0x0B, 0x0A,
+    0x15, 0xC9, 0x35, 0x64, 0xBA, 0x94, 0x4A, 0xB5, 0xCA, 0xD2, 0xA5, 0x97, 0x77, 0x8D, 0xA1, 0x6F,
+    0x3D, 0x03, 0x72, 0xE5, 0x18, 0x06, 0xC5, 0x52, 0xD9, 0x26, 0x8B, 0x25, 0x6E, 0x5D, 0x2A, 0x5A,
+    0xD8, 0xE7, 0x4A, 0xC3, 0x57, 0xBA, 0x08, 0x0C, 0x56, 0x9F, 0x41, 0x42, 0x6E, 0x9E, 0xDA, 0xF0,
+    0x59, 0xED, 0x01, 0xDA, 0x05, 0xED, 0xFA, 0xEF, 0xA0, 0x87, 0xBB, 0x1E, 0xCE, 0x5B, 0x7D, 0x54,
+    0x64, 0x7D, 0x89, 0xD5, 0x09, 0xA3, 0x4F, 0x96, 0xEF, 0x9E, 0x61, 0x04, 0x3B, 0xF4, 0xC9, 0x76,
+    0xC1, 0x1E, 0xEE, 0x11, 0x2B, 0xD9, 0x05, 0x7E, 0xDA, 0x1D, 0x05, 0xD1, 0x09, 0xB8, 0x1B, 0x51,
+    0x59, 0x35, 0x60, 0x7F, 0xDC, 0xF4, 0xE8, 0xD9, 0xC5, 0x81, 0x81, 0xEC, 0x0E, 0x93, 0x71, 0x6A,
+    0x2A, 0x12, 0xD4, 0x0A, 0x4E, 0xD0, 0x25, 0x3A, 0xE8, 0xE0, 0x2E, 0x9D, 0xE7, 0x56, 0xB8, 0x31,
+    0x61, 0x9B, 0x0E, 0x9B, 0x2E, 0xB9, 0xED, 0xE3, 0xED, 0xD7, 0xFE, 0x03,
+];
+
+#[test]
+fn import_deflated_bk2() {
+    let mut input = String::from("[Input]\nLogKey:#Reset|Power|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|\n");
+    for i in 0..64u32 {
+        let buttons = (i * i * 7 + i * 3) as u8;
+        let field: String = "UDLRSsBA"
+            .chars()
+            .enumerate()
+            .map(|(j, c)| if buttons >> j & 1 == 1 { c } else { '.' })
+            .collect();
+        input += &format!("|..|{}|\n", field);
+    }
+    input += "[/Input]\n";
+
+    let deflated = zip(&[
+        ("Header.txt", 0, BK2_HEADER.as_bytes()),
+        ("Input Log.txt", 8, &BK2_DEFLATED_INPUT),
+    ]);
+    let movie = Movie::from_bk2(&deflated).unwrap();
+    assert_eq!(movie.frames.len(), 64);
+    assert_eq!(movie.ports, [Port::Gamepad, Port::None]);
+    assert_eq!(
+        movie,
+        Movie::from_bk2(&zip(&[
+            ("Header.txt", 0, BK2_HEADER.as_bytes()),
+            ("Input Log.txt", 0, input.as_bytes()),
+        ]))
+        .unwrap()
+    );
+}
+
+#[test]
+fn unsupported_bk2() {
+    let unsupported = |header: &str, input: &str| {
+        matches!(
+            Movie::from_bk2(&bk2(header, input)),
+            Err(Error::Unsupported(_))
+        )
+    };
+    assert!(unsupported(
+        &BK2_HEADER.replace("Platform NES", "Platform SNES"),
+        BK2_INPUT
+    ));
+    assert!(unsupported(
+        &(BK2_HEADER.to_string() + "StartsFromSavestate True\n"),
+        BK2_INPUT
+    ));
+    assert!(unsupported(
+        BK2_HEADER,
+        &BK2_INPUT.replace("#P2 Up", "#P3 Up")
+    ));
+    assert!(unsupported(
+        BK2_HEADER,
+        &BK2_INPUT.replace("P2 A|", "P2 Fire|")
+    ));
+    assert!(matches!(
+        Movie::from_bk2(b"not a zip"),
+        Err(Error::Parse { .. })
+    ));
+}
+
+const MESEN_SETTINGS: &str = "\
+MesenVersion 0.9.9
+MovieFormatVersion 1
+GameFile test
+SHA1 0000000000000000000000000000000000000000
+Region NTSC
+ConsoleType Nes
+Controller1 StandardController
+Controller2 None
+Controller3 None
+Controller4 None
+ExpansionDevice None
+";
+
+const MESEN_INPUT: &str = "\
+|..|........
+|..|.......A
+|R.|U...S...
+|.P|..L..sB.
+";
+
+fn mmo(settings: &str) -> Vec<u8> {
+    zip(&[
+        ("GameSettings.txt", 0, settings.as_bytes()),
+        ("Input.txt", 0, MESEN_INPUT.as_bytes()),
+    ])
+}
+
+#[test]
+fn import_mesen() {
+    let movie = Movie::from_mesen(&mmo(MESEN_SETTINGS)).unwrap();
+
+    assert_eq!(movie.rom_filename, "test");
+    assert_eq!(movie.ports, [Port::Gamepad, Port::None]);
+    assert_eq!(movie.init_policy, InitPolicy::Zeros);
+
+    let frame = |command, buttons| Frame {
+        command,
+        buttons: [buttons, Buttons::empty()],
+    };
+    assert_eq!(
+        movie.frames,
+        [
+            frame(Command::empty(), Buttons::empty()),
+            frame(Command::empty(), Buttons::A),
+            frame(Command::SOFT_RESET, Buttons::UP | Buttons::START),
+            frame(Command::POWER, Buttons::LEFT | Buttons::SELECT | Buttons::B),
+        ]
+    );
+
+    play(&movie).unwrap();
+
+    let settings = MESEN_SETTINGS.to_string() + "RamPowerOnState AllOnes\n";
+    let movie = Movie::from_mesen(&mmo(&settings)).unwrap();
+    assert_eq!(movie.init_policy, InitPolicy::Ones);
+}
+
+#[test]
+fn unsupported_mesen() {
+    let unsupported = |from: &str, to: &str| {
+        let settings = MESEN_SETTINGS.replace(from, to);
+        matches!(
+            Movie::from_mesen(&mmo(&settings)),
+            Err(Error::Unsupported(_))
+        )
+    };
+    assert!(unsupported("Controller2 None", "Controller2 Zapper"));
+    assert!(unsupported(
+        "Controller3 None",
+        "Controller3 StandardController"
+    ));
+    assert!(unsupported(
+        "ExpansionDevice None",
+        "ExpansionDevice ArkanoidController"
+    ));
+    assert!(unsupported("Region NTSC", "Region PAL"));
+    assert!(unsupported(
+        "Region NTSC",
+        "Region NTSC\nRamPowerOnState Random"
+    ));
+
+    let from_savestate = zip(&[
+        ("GameSettings.txt", 0, MESEN_SETTINGS.as_bytes()),
+        ("Input.txt", 0, MESEN_INPUT.as_bytes()),
+        ("SaveState.mst", 0, b""),
+    ]);
+    assert!(matches!(
+        Movie::from_mesen(&from_savestate),
+        Err(Error::Unsupported(_))
+    ));
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::header;
+use korones::cartridge::Mirroring;
+use korones::{InitPolicy, Nes};
+
+// LDA/STA for each write, then `tail`, with battery-backed PRG RAM and sound RAM
+fn run(writes: &[(u16, u8)], tail: &[u8], handler: &[u8]) -> Nes {
+    let mut header = header(19, 0);
+    header[6] |= 0x02;
+    common::run(&header, writes, tail, handler)
+}
+
+#[test]
+fn prg_banks() {
+    let nes = run(&[(0xE000, 3), (0xE800, 4), (0xF000, 5)], &[], &[]);
+    assert_eq!(nes.peek(0x8000), 3);
+    assert_eq!(nes.peek(0xA000), 4);
+    assert_eq!(nes.peek(0xC000), 5);
+    assert_eq!(nes.peek(0xE000), 0xA9);
+}
+
+#[test]
+fn prg_ram_protect() {
+    let nes = run(
+        &[
+            (0x6000, 0x11),
+            (0xF800, 0x40),
+            (0x6000, 0x22),
+            // $6000-$67FF protected
+            (0xF800, 0x41),
+            (0x6001, 0x33),
+            (0x6800, 0x44),
+        ],
+        &[],
+        &[],
+    );
+    assert_eq!(nes.peek(0x6000), 0x22);
+    assert_eq!(nes.peek(0x6001), 0x00);
+    assert_eq!(nes.peek(0x6800), 0x44);
+}
+
+#[test]
+fn irq() {
+    // $7FF0, 15 cycles from the IRQ
+    #[rustfmt::skip]
+    let handler = [
+        0xE6, 0x00,       // INC $00
+        0xA9, 0x00,       // LDA #$00
+        0x8D, 0x00, 0x58, // STA $5800
+        0x40,             // RTI
+    ];
+    let mut nes = run(&[(0x5000, 0xF0), (0x5800, 0xFF)], &[0x58], &handler);
+    nes.run_frame();
+    assert_eq!(nes.peek(0x0000), 1);
+    // stopped at $7FFF, then disabled by the handler
+    assert_eq!(nes.peek(0x5000), 0xFF);
+    assert_eq!(nes.peek(0x5800), 0x00);
+}
+
+#[test]
+fn wavetable() {
+    let mut writes = vec![(0xF800, 0x80), (0x4800, 0xFF)];
+    // the last channel: a wave of 4 samples at $00, stopped, at full volume, alone
+    writes.extend([
+        (0xF800, 0xFC),
+        (0x4800, 0xFC),
+        (0x4800, 0x00),
+        (0x4800, 0x00),
+    ]);
+    writes.push((0x4800, 0x0F));
+    let mut nes = run(&writes, &[], &[]);
+    // until the channel is next updated
+    for _ in 0..10 {
+        nes.step();
+    }
+    let cartridge = nes.cartridge().unwrap();
+    assert_eq!(cartridge.audio_output(), 1.0);
+
+    // the internal RAM is saved after PRG RAM
+    let data = cartridge.save_data().unwrap();
+    assert_eq!(data.len(), 0x2080);
+    assert_eq!(data[0x2000], 0xFF);
+    assert_eq!(data[0x207C..], [0xFC, 0x00, 0x00, 0x0F]);
+
+    // silenced by $E000
+    let nes = run(&[writes, vec![(0xE000, 0x40)]].concat(), &[], &[]);
+    assert_eq!(nes.cartridge().unwrap().audio_output(), 0.0);
+}
+
+#[test]
+fn nametables() {
+    let mut nes = run(&[(0xC000, 5)], &[], &[]);
+    let mut cartridge = nes.eject_cartridge().unwrap().unwrap();
+    // CHR ROM, then CIRAM
+    assert_eq!(cartridge.nametable_read(0x2000), Some(5));
+    assert_eq!(cartridge.nametable_read(0x2400), None);
+
+    let nes = run(
+        &[
+            (0xC000, 0xE0),
+            (0xC800, 0xE0),
+            (0xD000, 0xE1),
+            (0xD800, 0xE1),
+        ],
+        &[],
+        &[],
+    );
+    assert_eq!(nes.cartridge().unwrap().mirroring(), Mirroring::Horizontal);
+}
+
+#[test]
+fn power_cycle() {
+    let mut nes = run(
+        &[(0xE000, 3), (0xF800, 0x40), (0x6000, 0x11), (0x4800, 0x22)],
+        &[],
+        &[],
+    );
+    nes.set_init_policy(InitPolicy::Ones);
+    nes.power_on();
+    assert_eq!(nes.peek(0x8000), 0);
+    // both RAMs are battery-backed
+    let data = nes.cartridge().unwrap().save_data().unwrap();
+    assert_eq!(data[0], 0x11);
+    assert_eq!(data[0x2040], 0x22);
+}
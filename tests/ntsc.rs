@@ -0,0 +1,143 @@
+use korones::ntsc::{NtscFilter, NtscSetup};
+use korones::palette::{Palette, Rgb};
+
+const WIDTH: usize = 32;
+const HEIGHT: usize = 6;
+
+fn frame(pixel: impl Fn(usize, usize) -> u16) -> Vec<u16> {
+    (0..WIDTH * HEIGHT)
+        .map(|n| pixel(n % WIDTH, n / WIDTH))
+        .collect()
+}
+
+// Output pixels away from the line ends, where blanking bleeds in
+fn interior(out: &[Rgb]) -> impl Iterator<Item = (usize, &Rgb)> {
+    let width = NtscFilter::output_width(WIDTH);
+    out.iter()
+        .enumerate()
+        .filter(move |(n, _)| (8..width - 8).contains(&(n % width)))
+}
+
+#[test]
+fn output_size() {
+    let filter = NtscFilter::new(NtscSetup::COMPOSITE);
+    let out = filter.apply(&frame(|_, _| 0x21), WIDTH, 0);
+    assert_eq!(NtscFilter::output_width(WIDTH), WIDTH * 2);
+    assert_eq!(out.len(), WIDTH * 2 * HEIGHT);
+    assert!(filter.apply(&[0x21; 4], 0, 0).is_empty());
+}
+
+#[test]
+fn flat_colors_without_artifacts_match_the_palette() {
+    let palette = Palette::ntsc(&NtscSetup::RGB.picture);
+    let filter = NtscFilter::new(NtscSetup::RGB);
+
+    for pixel in [0x00, 0x0F, 0x16, 0x21, 0x2A, 0x30, 0x16 | 0b101 << 6] {
+        let out = filter.apply(&frame(|_, _| pixel), WIDTH, 0);
+        let expected = palette.color(pixel as u8, (pixel >> 1) as u8 & 0xE0);
+        for (n, rgb) in interior(&out) {
+            let close = |a: u8, b: u8| a.abs_diff(b) <= 1;
+            assert!(
+                close(rgb.r, expected.r) && close(rgb.g, expected.g) && close(rgb.b, expected.b),
+                "{:03X} at {}: {:?} != {:?}",
+                pixel,
+                n,
+                rgb,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn composite_artifacts_crawl() {
+    let filter = NtscFilter::new(NtscSetup::COMPOSITE);
+    let pixels = frame(|_, _| 0x16);
+
+    let field0 = filter.apply(&pixels, WIDTH, 0);
+    let field1 = filter.apply(&pixels, WIDTH, 1);
+    // Dot pattern on flat colors, moving from field to field
+    assert!(interior(&field0).any(|(n, rgb)| *rgb != field0[n - 1]));
+    assert_ne!(field0, field1);
+    // Deterministic
+    assert_eq!(field0, filter.apply(&pixels, WIDTH, 0));
+}
+
+#[test]
+fn fringing_colors_luma_edges() {
+    let stripes = frame(|x, _| if x % 2 == 0 { 0x30 } else { 0x0F });
+
+    let gray = |out: &[Rgb]| interior(out).all(|(_, c)| c.r == c.g && c.g == c.b);
+    assert!(gray(
+        &NtscFilter::new(NtscSetup::SVIDEO).apply(&stripes, WIDTH, 0)
+    ));
+    assert!(!gray(
+        &NtscFilter::new(NtscSetup::COMPOSITE).apply(&stripes, WIDTH, 0)
+    ));
+}
+
+// Every color on each line, under each combination of emphasis bits
+fn golden_frame() -> Vec<u16> {
+    (0..64 * 8)
+        .map(|n| {
+            let (x, y) = (n % 64, n / 64);
+            ((x * 5 + y * 3) % 64 + (y << 6)) as u16
+        })
+        .collect()
+}
+
+// FNV-1a of a filtered frame
+fn hash(out: &[Rgb]) -> u64 {
+    out.iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .fold(0xCBF29CE484222325, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001B3)
+        })
+}
+
+// Hashes of the golden frame filtered in burst phases 0 and 1, then with merged fields
+fn golden_hashes(setup: NtscSetup) -> [u64; 3] {
+    let pixels = golden_frame();
+    let merged = NtscSetup {
+        merge_fields: true,
+        ..setup
+    };
+    [
+        hash(&NtscFilter::new(setup).apply(&pixels, 64, 0)),
+        hash(&NtscFilter::new(setup).apply(&pixels, 64, 1)),
+        hash(&NtscFilter::new(merged).apply(&pixels, 64, 0)),
+    ]
+}
+
+#[test]
+fn composite_golden_frames() {
+    assert_eq!(
+        golden_hashes(NtscSetup::COMPOSITE),
+        [0x33AAE1FAE9223C68, 0x08894BF984632CAF, 0x2DE418F212E1EA38]
+    );
+}
+
+#[test]
+fn svideo_golden_frames() {
+    assert_eq!(
+        golden_hashes(NtscSetup::SVIDEO),
+        [0x6F6D52EE6FC00AA0, 0x93937D4BB053EE2F, 0x4BB77164C198687C]
+    );
+}
+
+#[test]
+fn rgb_golden_frames() {
+    assert_eq!(
+        golden_hashes(NtscSetup::RGB),
+        [0xBB79142AB0A88ADB, 0x5A5B43E6F2D437F4, 0xDABAF185486C725E]
+    );
+}
+
+#[test]
+fn monochrome_golden_frames() {
+    // without chroma, nothing depends on the burst phase
+    assert_eq!(
+        golden_hashes(NtscSetup::MONOCHROME),
+        [0x0C1EE3C8C8D3858C; 3]
+    );
+}
@@ -0,0 +1,96 @@
+use korones::palette::{Error, NtscParams, Palette, Rgb, RgbPpu};
+
+const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+const WHITE: Rgb = Rgb {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+#[test]
+fn pal_file_with_64_entries() {
+    let bytes: Vec<u8> = (0..192).map(|n| n as u8).collect();
+    let palette = Palette::from_pal(&bytes).unwrap();
+
+    assert_eq!(palette.color(0x01, 0), Rgb { r: 3, g: 4, b: 5 });
+    // Red emphasis dims green and blue
+    let emphasized = palette.color(0x3F, 0x20);
+    assert_eq!(emphasized.r, 189);
+    assert!(emphasized.g < 190 && emphasized.b < 191);
+}
+
+#[test]
+fn pal_file_with_512_entries() {
+    let bytes: Vec<u8> = (0..1536).map(|n| (n * 7) as u8).collect();
+    let palette = Palette::from_pal(&bytes).unwrap();
+
+    assert_eq!(palette.to_pal(), bytes);
+    let n = (0b101 << 6 | 0x12) * 3;
+    assert_eq!(
+        palette.color(0x12, 0b1010_0000),
+        Rgb {
+            r: bytes[n],
+            g: bytes[n + 1],
+            b: bytes[n + 2],
+        }
+    );
+}
+
+#[test]
+fn pal_file_with_invalid_size() {
+    assert_eq!(Palette::from_pal(&[0; 100]), Err(Error::InvalidSize(100)));
+}
+
+#[test]
+fn ntsc_palette() {
+    let palette = Palette::default();
+
+    assert_eq!(palette.color(0x0F, 0), BLACK);
+    assert_eq!(palette.color(0x1D, 0), BLACK);
+    assert_eq!(palette.color(0x20, 0), WHITE);
+    assert_eq!(palette.color(0x30, 0), WHITE);
+    // $x0 and $xD are grays
+    let gray = palette.color(0x00, 0);
+    assert!(gray.r == gray.g && gray.g == gray.b);
+
+    // Blue emphasis dims the other colors
+    let white = palette.color(0x30, 0x80);
+    assert!(white.r < 255 && white.g < 255 && 250 < white.b);
+}
+
+#[test]
+fn ntsc_palette_parameters() {
+    let dark = Palette::ntsc(&NtscParams {
+        brightness: 0.5,
+        ..NtscParams::default()
+    });
+    assert!(dark.color(0x30, 0).r < 255);
+
+    let gray = Palette::ntsc(&NtscParams {
+        saturation: 0.0,
+        ..NtscParams::default()
+    });
+    let c = gray.color(0x16, 0);
+    assert!(c.r == c.g && c.g == c.b);
+}
+
+#[test]
+fn rgb_ppu_palette() {
+    let palette = Palette::rgb_ppu(RgbPpu::Rp2C03);
+
+    assert_eq!(palette.color(0x16, 0), Rgb { r: 255, g: 0, b: 0 });
+    // Emphasis turns the channel fully on
+    assert_eq!(palette.color(0x0F, 0b1110_0000), WHITE);
+    assert_eq!(palette.color(0x0F, 0b0100_0000), Rgb { r: 0, g: 255, b: 0 });
+}
+
+#[test]
+fn rp2c04_palettes() {
+    // the same colors as the 2C03, in another order
+    let color = |ppu, color| Palette::rgb_ppu(ppu).color(color, 0);
+    let rgb = |r, g, b| Rgb { r, g, b };
+    assert_eq!(color(RgbPpu::Rp2C04_0001, 0x00), rgb(255, 182, 182));
+    assert_eq!(color(RgbPpu::Rp2C04_0002, 0x01), rgb(255, 182, 0));
+    assert_eq!(color(RgbPpu::Rp2C04_0003, 0x00), rgb(182, 0, 255));
+    assert_eq!(color(RgbPpu::Rp2C04_0004, 0x00), rgb(145, 109, 0));
+}
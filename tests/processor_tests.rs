@@ -0,0 +1,157 @@
+// Single-step tests from https://github.com/SingleStepTests/ProcessorTests
+//
+// Each `<cpu>/v1/XX.json` file holds 10,000 cases for opcode XX with the CPU state before and after
+// executing one instruction, and the bus activity of every cycle in between.
+//
+// The test vectors aren't bundled with this crate, so these tests are ignored by default. Clone the
+// repository into `tests/ProcessorTests/` or point `PROCESSOR_TESTS_DIR` to it, then run
+//
+//     cargo test --release --test processor_tests -- --ignored
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use common::flat_memory::FlatMemory;
+use korones::cpu::{Cpu, Status, Variant};
+
+#[derive(Debug, Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+// Opcodes the NMOS core doesn't implement yet
+const UNSUPPORTED: &[u8] = &[
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2, // JAM
+    0x0B, 0x2B, // ANC
+    0x4B, // ALR
+    0x6B, // ARR
+    0x8B, // XAA
+    0xAB, // LXA
+    0xCB, // AXS
+    0x93, 0x9F, // SHA
+    0x9C, // SHY
+    0x9E, // SHX
+    0x9B, // TAS
+    0xBB, // LAS
+];
+
+fn run(variant: Variant, case: &TestCase) -> Result<(), String> {
+    let mut cpu = Cpu::new(variant);
+    cpu.a = case.initial.a;
+    cpu.x = case.initial.x;
+    cpu.y = case.initial.y;
+    cpu.s = case.initial.s;
+    cpu.p = Status::from_bits_truncate(case.initial.p);
+    cpu.pc = case.initial.pc;
+    let mut mem = FlatMemory::recording(vec![0; 0x10000]);
+    for &(addr, v) in &case.initial.ram {
+        mem.ram[addr as usize] = v;
+    }
+
+    cpu.step(&mut mem);
+
+    let e = &case.expected;
+    let registers = |cpu: &Cpu| (cpu.pc, cpu.s, cpu.a, cpu.x, cpu.y, cpu.p.bits());
+    let actual = registers(&cpu);
+    let expected = (e.pc, e.s, e.a, e.x, e.y, e.p);
+    if actual != expected {
+        return Err(format!(
+            "registers (pc, s, a, x, y, p): expected {:?}, but got {:?}",
+            expected, actual
+        ));
+    }
+    for &(addr, v) in &e.ram {
+        let actual = mem.ram[addr as usize];
+        if actual != v {
+            return Err(format!(
+                "ram[{:04X}]: expected {:02X}, but got {:02X}",
+                addr, v, actual
+            ));
+        }
+    }
+    let cycles = mem.cycles.unwrap();
+    if cycles != case.cycles {
+        return Err(format!(
+            "cycles: expected {:?}, but got {:?}",
+            case.cycles, cycles
+        ));
+    }
+    if mem.ticks != case.cycles.len() {
+        return Err(format!(
+            "cycle count: expected {}, but got {}",
+            case.cycles.len(),
+            mem.ticks
+        ));
+    }
+    Ok(())
+}
+
+fn run_all(variant: Variant, cpu: &str, unsupported: &[u8]) {
+    let dir = std::env::var_os("PROCESSOR_TESTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/ProcessorTests"))
+        .join(cpu)
+        .join("v1");
+    assert!(
+        dir.is_dir(),
+        "{} not found, see tests/processor_tests.rs",
+        dir.display()
+    );
+
+    let mut failures = Vec::new();
+    for op in 0..=0xFFu8 {
+        if unsupported.contains(&op) {
+            continue;
+        }
+        let path = dir.join(format!("{:02x}.json", op));
+        let json = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+        let cases: Vec<TestCase> = serde_json::from_str(&json)
+            .unwrap_or_else(|e| panic!("failed to parse {}: {}", path.display(), e));
+
+        // Report only the first failure per opcode
+        if let Some((case, err)) = cases
+            .iter()
+            .find_map(|case| run(variant, case).err().map(|err| (case, err)))
+        {
+            failures.push(format!("{:02X} \"{}\": {}", op, case.name, err));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+#[ignore = "needs the ProcessorTests vectors"]
+fn nes6502() {
+    run_all(Variant::Ricoh2A03, "nes6502", UNSUPPORTED);
+}
+
+#[test]
+#[ignore = "needs the ProcessorTests vectors"]
+fn nmos6502() {
+    run_all(Variant::Nmos6502, "6502", UNSUPPORTED);
+}
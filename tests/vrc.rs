@@ -0,0 +1,194 @@
+mod common;
+
+use common::{header, program, record, run_until};
+use korones::cartridge::Cartridge;
+use korones::Nes;
+
+fn cartridge(mapper: u16, submapper: u8, code: &[u8], handler: &[u8]) -> Cartridge {
+    common::cartridge(&header(mapper, submapper), code, handler)
+}
+
+fn run(mapper: u16, submapper: u8, writes: &[(u16, u8)]) -> Nes {
+    common::run(&header(mapper, submapper), writes, &[], &[])
+}
+
+#[test]
+fn vrc4_prg_banks() {
+    // VRC4c, registers selected by A6 and A7
+    let nes = run(21, 2, &[(0x8000, 3), (0xA000, 4)]);
+    assert_eq!(nes.peek(0x8000), 3);
+    assert_eq!(nes.peek(0xA000), 4);
+    assert_eq!(nes.peek(0xC000), 14);
+
+    // $9002, the swap mode
+    let nes = run(21, 2, &[(0x8000, 3), (0x9080, 0x02)]);
+    assert_eq!(nes.peek(0x8000), 14);
+    assert_eq!(nes.peek(0xC000), 3);
+    // but not $9001 on VRC4a
+    let nes = run(21, 1, &[(0x8000, 3), (0x9002, 0x02)]);
+    assert_eq!(nes.peek(0x8000), 3);
+}
+
+#[test]
+fn vrc4_chr_banks() {
+    // VRC4d, registers selected by A3 and A2: $B000 then $B001 for bank 0, $B002 and $B003 for 1
+    let mut nes = run(25, 2, &[(0xB000, 0x01), (0xB008, 0x02), (0xB004, 0x05)]);
+    let mut cartridge = nes.eject_cartridge().unwrap().unwrap();
+    assert_eq!(cartridge.ppu_read(0x0000), 0x21);
+    assert_eq!(cartridge.ppu_read(0x0400), 0x05);
+}
+
+#[test]
+fn vrc2() {
+    // VRC2a ignores the low bit of CHR banks, and has a latch instead of PRG RAM
+    let mut nes = run(22, 0, &[(0xB000, 0x05), (0x6000, 0x01), (0x7000, 0x01)]);
+    assert_eq!(nes.peek(0x6000) & 1, 1);
+    assert_eq!(nes.peek(0x7000), 0x70);
+    let mut cartridge = nes.eject_cartridge().unwrap().unwrap();
+    assert_eq!(cartridge.ppu_read(0x0000), 2);
+}
+
+#[test]
+fn vrc_irq_cycle_mode() {
+    // latch $FC, counting CPU cycles: four cycles to $FF and the IRQ
+    let (code, end) = program(
+        &[(0xF000, 0x0C), (0xF001, 0x0F), (0xF002, 0x06)],
+        &[0x58], // CLI
+    );
+    #[rustfmt::skip]
+    let handler = [
+        0xE6, 0x00,       // INC $00
+        0x8D, 0x03, 0xF0, // STA $F003
+        0x40,             // RTI
+    ];
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(23, 1, &code, &handler));
+    nes.power_on();
+    run_until(&mut nes, end);
+    nes.run_frame();
+    // the acknowledge disables it again
+    assert_eq!(nes.peek(0x0000), 1);
+}
+
+#[test]
+fn vrc_irq_scanline_mode() {
+    let (code, end) = program(&[(0xF000, 0x0E), (0xF001, 0x0F), (0xF002, 0x03)], &[0x58]);
+    #[rustfmt::skip]
+    let handler = [
+        0xE6, 0x00,       // INC $00
+        0x8D, 0x03, 0xF0, // STA $F003
+        0x40,             // RTI
+    ];
+    let mut nes = Nes::new();
+    nes.insert_cartridge(cartridge(23, 1, &code, &handler));
+    nes.power_on();
+    run_until(&mut nes, end);
+    // every two scanlines of 341/3 cycles, with the acknowledge enabling it again
+    nes.run_frame();
+    let irqs = nes.peek(0x0000);
+    assert!((130..=132).contains(&irqs), "{}", irqs);
+}
+
+#[test]
+fn vrc6() {
+    let nes = run(
+        24,
+        0,
+        &[(0x8000, 2), (0xC000, 5), (0xB003, 0x80), (0x6000, 0x42)],
+    );
+    assert_eq!(nes.peek(0x8000), 4);
+    assert_eq!(nes.peek(0xA000), 5);
+    assert_eq!(nes.peek(0xC000), 5);
+    assert_eq!(nes.peek(0xE000), 0xA9);
+    assert_eq!(nes.peek(0x6000), 0x42);
+}
+
+#[test]
+fn vrc6_audio() {
+    // pulse 1 at constant full volume
+    let nes = run(24, 0, &[(0x9000, 0x8F), (0x9002, 0x80)]);
+    let output = nes.cartridge().unwrap().audio_output();
+    assert_eq!(output, 15.0 / 61.0);
+
+    // mapper 26 swaps A0 and A1: $9002 is at $9001
+    let nes = run(26, 0, &[(0x9000, 0x8F), (0x9001, 0x80)]);
+    assert_eq!(nes.cartridge().unwrap().audio_output(), 15.0 / 61.0);
+
+    // the sawtooth ramps up
+    let mut nes = run(24, 0, &[(0xB000, 0x3F), (0xB002, 0x80)]);
+    let levels = record(&mut nes, 10);
+    assert!(levels.iter().any(|&l| l > 0.0), "{:?}", levels);
+}
+
+#[test]
+fn vrc7() {
+    // VRC7a, the second register of a pair at A4
+    let nes = run(
+        85,
+        2,
+        &[
+            (0x8000, 3),
+            (0x8010, 4),
+            (0x9000, 5),
+            (0xA010, 7),
+            (0xE000, 0x80),
+            (0x6000, 0x42),
+        ],
+    );
+    assert_eq!(nes.peek(0x8000), 3);
+    assert_eq!(nes.peek(0xA000), 4);
+    assert_eq!(nes.peek(0xC000), 5);
+    assert_eq!(nes.peek(0xE000), 0xA9);
+    assert_eq!(nes.peek(0x6000), 0x42);
+    let mut cartridge = nes.cartridge().unwrap().clone();
+    assert_eq!(cartridge.ppu_read(0x0400), 7);
+}
+
+// Changes of sign of the FM output over `cycles` CPU cycles
+fn fm_crossings(nes: &mut Nes, cycles: u128) -> usize {
+    let end = nes.cpu_cycles() + cycles;
+    let mut crossings = 0;
+    let mut positive = false;
+    while nes.cpu_cycles() < end {
+        nes.step();
+        let output = nes.cartridge().unwrap().audio_output();
+        if (output > 0.5) != positive {
+            positive = !positive;
+            crossings += 1;
+        }
+    }
+    crossings
+}
+
+#[test]
+fn vrc7_fm() {
+    // a sine on the carrier, the modulator fully attenuated, released quickly
+    let instrument = [0x20, 0x21, 0x3F, 0x00, 0xF0, 0xF0, 0x00, 0x0F];
+    let mut writes = vec![];
+    for (register, value) in instrument.into_iter().enumerate() {
+        writes.extend([(0x9010, register as u8), (0x9030, value)]);
+    }
+    // channel 0 at full volume, F-number 256 in block 4, key on
+    writes.extend([
+        (0x9010, 0x30),
+        (0x9030, 0x00),
+        (0x9010, 0x10),
+        (0x9030, 0x00),
+    ]);
+    writes.extend([(0x9010, 0x20), (0x9030, 0x19)]);
+    let mut nes = run(85, 2, &writes);
+
+    // 49716Hz * 256 * 2^4 / 2^19 = 388.4Hz, two crossings a period over a tenth of a second
+    let crossings = fm_crossings(&mut nes, 178_977);
+    assert!((76..=80).contains(&crossings), "{}", crossings);
+
+    // released once the key is off, then silent
+    let mut nes = run(
+        85,
+        2,
+        &[writes, vec![(0x9010, 0x20), (0x9030, 0x09)]].concat(),
+    );
+    nes.run_frame();
+    assert_eq!(fm_crossings(&mut nes, 1000), 0);
+    assert_eq!(nes.cartridge().unwrap().audio_output(), 0.5);
+}